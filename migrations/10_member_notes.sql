@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS member_notes (
+    id SERIAL PRIMARY KEY,
+    member_id INT REFERENCES members (id) NOT NULL,
+    parent_id INT REFERENCES member_notes (id) ON DELETE CASCADE NULL,
+    account_id INT REFERENCES accounts (id) NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX IF NOT EXISTS member_notes_member_id_idx ON member_notes (member_id, created_at);
+
+-- Split the old `=== date ===` blocks in members.notes into one note per block
+INSERT INTO member_notes (member_id, body, created_at)
+SELECT
+    id,
+    TRIM(E'\n' FROM COALESCE(SUBSTRING(chunk FROM E'^=== \\d{4}-\\d{2}-\\d{2} ===\\n(.*)$'), chunk)),
+    COALESCE(SUBSTRING(chunk FROM E'^=== (\\d{4}-\\d{2}-\\d{2}) ===')::DATE, created_on)
+FROM
+    members,
+    REGEXP_SPLIT_TO_TABLE(TRIM(E'\n' FROM notes), E'\\n\\n(?==== \\d{4}-\\d{2}-\\d{2} ===)') AS chunk
+WHERE TRIM(E'\n' FROM notes) <> ''
+ORDER BY id, 3;
+
+ALTER TABLE members DROP COLUMN IF EXISTS notes;
@@ -0,0 +1,129 @@
+ALTER TABLE payments
+ADD COLUMN IF NOT EXISTS voided BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN IF NOT EXISTS void_reason TEXT NULL;
+
+CREATE OR REPLACE FUNCTION is_active(member_id_arg INTEGER) RETURNS BOOLEAN
+LANGUAGE SQL
+STABLE STRICT
+AS $$
+    SELECT
+        (NOT banned)
+        AND EXISTS (
+            SELECT id
+            FROM payments
+            WHERE
+                member_id = member_id_arg
+                AND NOT voided
+                AND effective_on + (
+                    INTERVAL '1 month' * duration_months
+                ) + (INTERVAL '7 days') >= NOW()
+        )
+    FROM members
+    WHERE
+        id = member_id_arg;
+$$;
+
+CREATE OR REPLACE FUNCTION consecutive_since(member_id_arg INTEGER) RETURNS DATE
+LANGUAGE PLPGSQL
+STABLE STRICT
+AS $$
+    DECLARE
+        earliest DATE := NULL;
+        candidate DATE := NULL;
+    BEGIN
+        SELECT effective_on
+            FROM payments
+            WHERE member_id = member_id_arg AND NOT voided
+            ORDER BY (effective_on + INTERVAL '1 month' * duration_months) DESC
+            LIMIT 1
+            INTO candidate;
+
+        WHILE candidate IS NOT NULL LOOP
+            earliest := candidate;
+            SELECT effective_on
+                FROM payments
+                WHERE
+                    member_id = member_id_arg
+                    AND NOT voided
+                    AND effective_on < earliest
+                    AND effective_on + INTERVAL '1 month' * duration_months + INTERVAL '7 days' >= earliest
+                ORDER BY effective_on ASC
+                LIMIT 1
+                INTO candidate;
+        END LOOP;
+        RETURN earliest;
+    END;
+$$;
+
+CREATE OR REPLACE FUNCTION has_payment_gap(member_id_arg INTEGER) RETURNS BOOLEAN
+LANGUAGE SQL
+STABLE STRICT
+AS $$
+    SELECT effective_on <> consecutive_since(member_id_arg)
+    FROM payments
+    WHERE member_id = member_id_arg AND NOT voided
+    ORDER BY effective_on ASC
+    LIMIT 1
+$$;
+
+CREATE OR REPLACE FUNCTION consecutive_until(member_id_arg INTEGER) RETURNS DATE
+LANGUAGE SQL
+STABLE STRICT
+AS $$
+    SELECT (effective_on + INTERVAL '1 month' * duration_months)::DATE
+        FROM payments
+        WHERE member_id = member_id_arg AND NOT voided
+        ORDER BY (effective_on + INTERVAL '1 month' * duration_months) DESC
+        LIMIT 1
+$$;
+
+CREATE OR REPLACE VIEW member_details AS
+SELECT
+    members.id AS id,
+    generations.id AS "generation_id",
+    generations.title AS "generation_name",
+    consecutive_since_cached.cached_value AS "consecutive_since",
+    consecutive_until (members.id) AS "consecutive_until",
+    is_active (members.id) AS "is_active",
+    (
+        SELECT effective_on
+        FROM payments
+        WHERE
+            member_id = members.id
+            AND NOT voided
+        ORDER BY effective_on ASC
+        LIMIT 1
+    ) AS "first_payment"
+FROM
+    members
+    LEFT JOIN member_generations ON members.id = member_generations.member_id
+    LEFT JOIN generations ON generations.id = generation_id
+    LEFT JOIN consecutive_since_cached ON members.id = consecutive_since_cached.member_id;
+
+-- Recompute the cache for both the old and new member whenever a payment changes
+CREATE OR REPLACE FUNCTION update_consecutive_since_cached () RETURNS TRIGGER
+LANGUAGE PLPGSQL AS $$
+BEGIN
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        INSERT INTO consecutive_since_cached (member_id, cached_value)
+            VALUES (NEW.member_id, consecutive_since(NEW.member_id))
+            ON CONFLICT (member_id) DO UPDATE SET cached_value = consecutive_since(NEW.member_id);
+    END IF;
+    IF TG_OP = 'DELETE' OR (TG_OP = 'UPDATE' AND OLD.member_id <> NEW.member_id) THEN
+        INSERT INTO consecutive_since_cached (member_id, cached_value)
+            SELECT OLD.member_id, consecutive_since(OLD.member_id)
+            WHERE EXISTS (SELECT 1 FROM members WHERE id = OLD.member_id)
+            ON CONFLICT (member_id) DO UPDATE SET cached_value = consecutive_since(OLD.member_id);
+    END IF;
+    RETURN NULL;
+END; $$;
+
+DROP TRIGGER IF EXISTS update_consecutive_since_cache_on_insert ON payments;
+
+DROP TRIGGER IF EXISTS update_consecutive_since_cache_on_change ON payments;
+
+CREATE TRIGGER update_consecutive_since_cache_on_change
+AFTER INSERT OR UPDATE OR DELETE ON payments FOR EACH ROW
+EXECUTE FUNCTION update_consecutive_since_cached ();
+
+CALL reload_consecutive_since_cached ();
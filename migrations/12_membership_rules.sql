@@ -0,0 +1,105 @@
+CREATE TABLE IF NOT EXISTS membership_rules (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    grace_period_days INT NOT NULL DEFAULT 7 CHECK (grace_period_days >= 0)
+);
+
+INSERT INTO membership_rules (id) VALUES (TRUE) ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS membership_prices (
+    amount NUMERIC PRIMARY KEY CHECK (amount >= 0),
+    duration_months INT NOT NULL CHECK (duration_months > 0)
+);
+
+CREATE OR REPLACE FUNCTION grace_period() RETURNS INTERVAL
+LANGUAGE SQL
+STABLE
+AS $$
+    SELECT COALESCE(
+        (SELECT MAKE_INTERVAL(days => grace_period_days) FROM membership_rules),
+        INTERVAL '7 days'
+    );
+$$;
+
+-- The longest duration whose price is covered by the amount paid, or 1 month if none is
+CREATE OR REPLACE FUNCTION duration_for_amount(amount_arg NUMERIC) RETURNS INTEGER
+LANGUAGE SQL
+STABLE
+AS $$
+    SELECT COALESCE(
+        (
+            SELECT duration_months
+            FROM membership_prices
+            WHERE amount <= amount_arg
+            ORDER BY amount DESC
+            LIMIT 1
+        ),
+        1
+    );
+$$;
+
+CREATE OR REPLACE FUNCTION is_active(member_id_arg INTEGER) RETURNS BOOLEAN
+LANGUAGE SQL
+STABLE STRICT
+AS $$
+    SELECT
+        (NOT banned)
+        AND EXISTS (
+            SELECT id
+            FROM payments
+            WHERE
+                member_id = member_id_arg
+                AND NOT voided
+                AND effective_on + (
+                    INTERVAL '1 month' * duration_months
+                ) + grace_period() >= NOW()
+        )
+    FROM members
+    WHERE
+        id = member_id_arg;
+$$;
+
+CREATE OR REPLACE FUNCTION consecutive_since(member_id_arg INTEGER) RETURNS DATE
+LANGUAGE PLPGSQL
+STABLE STRICT
+AS $$
+    DECLARE
+        earliest DATE := NULL;
+        candidate DATE := NULL;
+        grace INTERVAL := grace_period();
+    BEGIN
+        SELECT effective_on
+            FROM payments
+            WHERE member_id = member_id_arg AND NOT voided
+            ORDER BY (effective_on + INTERVAL '1 month' * duration_months) DESC
+            LIMIT 1
+            INTO candidate;
+
+        WHILE candidate IS NOT NULL LOOP
+            earliest := candidate;
+            SELECT effective_on
+                FROM payments
+                WHERE
+                    member_id = member_id_arg
+                    AND NOT voided
+                    AND effective_on < earliest
+                    AND effective_on + INTERVAL '1 month' * duration_months + grace >= earliest
+                ORDER BY effective_on ASC
+                LIMIT 1
+                INTO candidate;
+        END LOOP;
+        RETURN earliest;
+    END;
+$$;
+
+CREATE OR REPLACE FUNCTION reload_consecutive_since_cached_on_rules_change () RETURNS TRIGGER
+LANGUAGE PLPGSQL AS $$
+BEGIN
+    CALL reload_consecutive_since_cached ();
+    RETURN NULL;
+END; $$;
+
+DROP TRIGGER IF EXISTS reload_consecutive_since_cached_on_rules_change ON membership_rules;
+
+CREATE TRIGGER reload_consecutive_since_cached_on_rules_change
+AFTER UPDATE ON membership_rules FOR EACH STATEMENT
+EXECUTE FUNCTION reload_consecutive_since_cached_on_rules_change ();
@@ -0,0 +1,67 @@
+CREATE TABLE IF NOT EXISTS plans (
+    id SERIAL PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    price NUMERIC NOT NULL DEFAULT 0 CHECK (price >= 0),
+    duration_months INT NOT NULL CHECK (duration_months > 0),
+    benefits TEXT NOT NULL DEFAULT '',
+    archived BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+ALTER TABLE payments
+ADD COLUMN IF NOT EXISTS plan_id INT REFERENCES plans (id) NULL;
+
+-- Webhook payments are linked to the plan sold at exactly the amount paid
+CREATE OR REPLACE FUNCTION plan_for_amount(amount_arg NUMERIC) RETURNS INTEGER
+LANGUAGE SQL
+STABLE
+AS $$
+    SELECT id
+    FROM plans
+    WHERE price = amount_arg AND NOT archived
+    ORDER BY duration_months DESC
+    LIMIT 1;
+$$;
+
+-- The plan of the most recent payment that is currently in effect
+CREATE OR REPLACE FUNCTION current_plan_id(member_id_arg INTEGER) RETURNS INTEGER
+LANGUAGE SQL
+STABLE STRICT
+AS $$
+    SELECT plan_id
+    FROM payments
+    WHERE
+        member_id = member_id_arg
+        AND NOT voided
+        AND effective_on <= NOW()
+        AND effective_on + (
+            INTERVAL '1 month' * duration_months
+        ) + grace_period() >= NOW()
+    ORDER BY effective_on DESC, id DESC
+    LIMIT 1;
+$$;
+
+CREATE OR REPLACE VIEW member_details AS
+SELECT
+    members.id AS id,
+    generations.id AS "generation_id",
+    generations.title AS "generation_name",
+    consecutive_since_cached.cached_value AS "consecutive_since",
+    consecutive_until (members.id) AS "consecutive_until",
+    is_active (members.id) AS "is_active",
+    (
+        SELECT effective_on
+        FROM payments
+        WHERE
+            member_id = members.id
+            AND NOT voided
+        ORDER BY effective_on ASC
+        LIMIT 1
+    ) AS "first_payment",
+    plans.id AS "current_plan_id",
+    plans.name AS "current_plan_name"
+FROM
+    members
+    LEFT JOIN member_generations ON members.id = member_generations.member_id
+    LEFT JOIN generations ON generations.id = generation_id
+    LEFT JOIN consecutive_since_cached ON members.id = consecutive_since_cached.member_id
+    LEFT JOIN plans ON plans.id = current_plan_id (members.id);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS discord_settings (
+    id TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS discord_verifications (
+    discord NUMERIC PRIMARY KEY,
+    member_id INT NOT NULL REFERENCES members (id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    assign_role NUMERIC NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '15 minutes'
+);
+
+INSERT INTO email_templates (id, template)
+VALUES (
+    'discord_verification',
+    '<p>Hi {first_name},</p><p>Your Discord verification code is <b>{verification_code}</b>. It expires in 15 minutes.</p><p>If you did not try to join our Discord server, you can safely ignore this email.</p>'
+)
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,5 @@
+-- Board members running admin commands in Discord act as the MDMA account linked here
+CREATE TABLE IF NOT EXISTS account_discord_ids (
+    discord NUMERIC PRIMARY KEY,
+    account_id INT NOT NULL UNIQUE REFERENCES accounts (id) ON DELETE CASCADE
+);
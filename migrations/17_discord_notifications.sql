@@ -0,0 +1,37 @@
+-- Membership lifecycle events waiting to be posted to the Discord notification channel
+CREATE TABLE IF NOT EXISTS discord_notifications (
+    id SERIAL PRIMARY KEY,
+    member_id INT NOT NULL REFERENCES members (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE OR REPLACE FUNCTION queue_member_notifications () RETURNS TRIGGER
+LANGUAGE PLPGSQL AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO discord_notifications (member_id, event) VALUES (NEW.id, 'member.create');
+        RETURN NULL;
+    END IF;
+
+    IF NEW.cancelled AND NOT OLD.cancelled THEN
+        INSERT INTO discord_notifications (member_id, event) VALUES (NEW.id, 'member.cancel');
+    ELSIF OLD.cancelled AND NOT NEW.cancelled THEN
+        INSERT INTO discord_notifications (member_id, event) VALUES (NEW.id, 'member.uncancel');
+    END IF;
+
+    IF NEW.banned AND NOT OLD.banned THEN
+        INSERT INTO discord_notifications (member_id, event) VALUES (NEW.id, 'member.ban');
+    ELSIF OLD.banned AND NOT NEW.banned THEN
+        INSERT INTO discord_notifications (member_id, event) VALUES (NEW.id, 'member.unban');
+    END IF;
+    RETURN NULL;
+END; $$;
+
+DROP TRIGGER IF EXISTS queue_member_notifications ON members;
+
+CREATE TRIGGER queue_member_notifications
+AFTER INSERT OR UPDATE OF cancelled, banned ON members
+FOR EACH ROW
+EXECUTE FUNCTION queue_member_notifications ();
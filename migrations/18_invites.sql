@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS invites (
+    code TEXT PRIMARY KEY,
+    member_id INT REFERENCES members (id) ON DELETE CASCADE NULL,
+    account_id INT REFERENCES accounts (id) NULL,
+    reason TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'used', 'expired')),
+    checked_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX IF NOT EXISTS invites_member_id_idx ON invites (member_id, created_at);
@@ -0,0 +1,20 @@
+-- Periodically synced copy of the Discord guild, used for searching and displaying linked accounts
+CREATE TABLE IF NOT EXISTS discord_roles (
+    id NUMERIC PRIMARY KEY,
+    name TEXT NOT NULL,
+    colour INT NOT NULL DEFAULT 0,
+    position INT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS discord_users (
+    id NUMERIC PRIMARY KEY,
+    username TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    avatar_url TEXT NOT NULL,
+    roles NUMERIC[] NOT NULL DEFAULT '{}',
+    joined_at TIMESTAMPTZ NULL,
+    in_guild BOOLEAN NOT NULL DEFAULT TRUE,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS discord_users_username_idx ON discord_users (LOWER(username));
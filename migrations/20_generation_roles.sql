@@ -0,0 +1 @@
+ALTER TABLE generations ADD COLUMN IF NOT EXISTS discord_role NUMERIC UNIQUE;
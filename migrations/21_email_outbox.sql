@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS email_outbox (
+    id SERIAL PRIMARY KEY,
+    member_id INT REFERENCES members (id) ON DELETE SET NULL NULL,
+    email_key TEXT NOT NULL,
+    to_address TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    envelope_from TEXT NULL,
+    envelope_to TEXT [] NOT NULL,
+    message BYTEA NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued' CHECK (status IN ('queued', 'sent', 'failed')),
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_queued_idx ON email_outbox (next_attempt_at)
+WHERE
+    status = 'queued';
+
+CREATE INDEX IF NOT EXISTS email_outbox_created_at_idx ON email_outbox (created_at);
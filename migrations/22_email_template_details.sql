@@ -0,0 +1,32 @@
+ALTER TABLE email_templates
+ADD COLUMN IF NOT EXISTS subject TEXT NOT NULL DEFAULT '',
+ADD COLUMN IF NOT EXISTS description TEXT NOT NULL DEFAULT '',
+ADD COLUMN IF NOT EXISTS plaintext TEXT NULL;
+
+-- Subjects used to be hardcoded where each email was sent
+INSERT INTO email_templates (id, template, subject, description)
+VALUES
+    (
+        'discord',
+        '<p>Hi {first_name},</p><p>Welcome to the club! Join our Discord server with this invite: <a href="{invite_url}">{invite_url}</a></p>',
+        'Psychedelic Club Discord',
+        'Discord invite sent to new members'
+    ),
+    (
+        'discord_verification',
+        '<p>Hi {first_name},</p><p>Your Discord verification code is <b>{verification_code}</b>. It expires in 15 minutes.</p>',
+        'Discord Verification Code',
+        'Code sent when a member links their Discord account'
+    ),
+    (
+        'board_notif',
+        '<p>New member: {first_name} {last_name} ({email}) paid {amount_paid}.</p>',
+        'New Member Notification',
+        'Sent to the board notification address for each new Donorbox member'
+    )
+ON CONFLICT (id) DO UPDATE
+SET
+    subject = excluded.subject,
+    description = excluded.description
+WHERE
+    email_templates.subject = '';
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS email_campaigns (
+    id SERIAL PRIMARY KEY,
+    account_id INT REFERENCES accounts (id) NULL,
+    email_key TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    template TEXT NOT NULL,
+    plaintext TEXT NULL,
+    segment JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE email_outbox
+ADD COLUMN IF NOT EXISTS campaign_id INT REFERENCES email_campaigns (id) ON DELETE SET NULL NULL;
+
+CREATE INDEX IF NOT EXISTS email_outbox_campaign_id_idx ON email_outbox (campaign_id)
+WHERE
+    campaign_id IS NOT NULL;
@@ -0,0 +1,21 @@
+-- Prefer the duration of the plan a payment is linked to, so the two never disagree
+CREATE OR REPLACE FUNCTION duration_for_amount(amount_arg NUMERIC) RETURNS INTEGER
+LANGUAGE SQL
+STABLE
+AS $$
+    SELECT COALESCE(
+        (
+            SELECT duration_months
+            FROM plans
+            WHERE id = plan_for_amount(amount_arg)
+        ),
+        (
+            SELECT duration_months
+            FROM membership_prices
+            WHERE amount <= amount_arg
+            ORDER BY amount DESC
+            LIMIT 1
+        ),
+        1
+    );
+$$;
@@ -0,0 +1,11 @@
+-- Codes sent and incorrect codes entered, used to rate limit and lock out verification by Discord user and email
+CREATE TABLE IF NOT EXISTS discord_verification_log (
+    id SERIAL PRIMARY KEY,
+    discord NUMERIC NOT NULL,
+    email TEXT NOT NULL,
+    event TEXT NOT NULL CHECK (event IN ('sent', 'failed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS discord_verification_log_email_idx ON discord_verification_log (LOWER(email), created_at);
+CREATE INDEX IF NOT EXISTS discord_verification_log_discord_idx ON discord_verification_log (discord, created_at);
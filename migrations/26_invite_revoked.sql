@@ -0,0 +1,3 @@
+-- Invites that disappear from the guild before expiring without Discord reporting a use were revoked
+ALTER TABLE invites DROP CONSTRAINT IF EXISTS invites_status_check;
+ALTER TABLE invites ADD CONSTRAINT invites_status_check CHECK (status IN ('pending', 'used', 'expired', 'revoked'));
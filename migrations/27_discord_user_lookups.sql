@@ -0,0 +1,6 @@
+-- Failed lookups of linked Discord users outside the guild, retried with exponential backoff
+CREATE TABLE IF NOT EXISTS discord_user_lookups (
+    id NUMERIC PRIMARY KEY,
+    failures INT NOT NULL DEFAULT 0,
+    retry_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS member_email_aliases (
+    email TEXT PRIMARY KEY,
+    member_id INT REFERENCES members (id) NOT NULL,
+    replaced_on DATE NOT NULL DEFAULT NOW()
+);
+
+CREATE OR REPLACE FUNCTION member_id_by_email(email_arg TEXT) RETURNS INTEGER
+LANGUAGE SQL
+STABLE STRICT
+AS $$
+    SELECT member_id FROM (
+        SELECT id AS member_id, 0 AS priority
+            FROM members
+            WHERE LOWER(email) = LOWER(email_arg)
+        UNION ALL
+        SELECT member_id, 1 AS priority
+            FROM member_email_aliases
+            WHERE email = LOWER(email_arg)
+    ) candidates
+    ORDER BY priority ASC
+    LIMIT 1
+$$;
@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS audit_events (
+    id SERIAL PRIMARY KEY,
+    account_id INT REFERENCES accounts (id) NULL,
+    member_id INT REFERENCES members (id) NULL,
+    action TEXT NOT NULL,
+    reason TEXT NULL,
+    before JSONB NULL,
+    after JSONB NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_member_id_idx ON audit_events (member_id, created_at);
+
+CREATE INDEX IF NOT EXISTS audit_events_created_at_idx ON audit_events (created_at);
+
+CREATE OR REPLACE FUNCTION uncancel_on_payment () RETURNS TRIGGER
+LANGUAGE PLPGSQL AS $$
+BEGIN
+    INSERT INTO audit_events (member_id, action, reason, before, after)
+        SELECT
+            id,
+            'member.uncancel',
+            'Automatically uncancelled from Payment ID ' || NEW.id,
+            jsonb_build_object('cancelled', TRUE),
+            jsonb_build_object('cancelled', FALSE)
+        FROM members
+        WHERE id = NEW.member_id AND cancelled;
+
+    UPDATE members
+    SET cancelled = FALSE
+    WHERE id = NEW.member_id AND cancelled;
+    RETURN NULL;
+END; $$;
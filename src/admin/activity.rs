@@ -0,0 +1,254 @@
+use axum::{
+    extract::{NestedPath, State},
+    http::HeaderMap,
+    response::Response,
+    routing::get,
+    Router,
+};
+use axum_extra::extract::Query;
+use maud::{html, Markup};
+use serde_json::Value;
+use tokio::try_join;
+
+use crate::{
+    components,
+    db::audit_events::{AuditEventRow, AuditEventsQuery},
+    err_responses::{ErrorResponse, MapErrorResponse},
+    icons,
+};
+
+struct SelectIdOption {
+    id: i32,
+    description: String,
+}
+
+pub struct PaginationRequest {
+    count: u64,
+    offset: u64,
+}
+
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::from("—"),
+        Some(Value::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn changed_fields(event: &AuditEventRow) -> Vec<(String, String, String)> {
+    let empty = serde_json::Map::new();
+    let before = match &event.before {
+        Some(Value::Object(map)) => map,
+        _ => &empty,
+    };
+    let after = match &event.after {
+        Some(Value::Object(map)) => map,
+        _ => &empty,
+    };
+
+    let mut keys = before.keys().chain(after.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| before.get(*key) != after.get(*key))
+        .map(|key| {
+            (
+                key.clone(),
+                display_value(before.get(key)),
+                display_value(after.get(key)),
+            )
+        })
+        .collect()
+}
+
+fn event_changes(event: &AuditEventRow) -> Markup {
+    let changes = changed_fields(event);
+    html! {
+        @if !changes.is_empty() {
+            ul ."text-sm"."font-mono" {
+                @for (key, before, after) in changes {
+                    li { b {(key)": "} span ."opacity-70" {(before)} " → " (after) }
+                }
+            }
+        }
+    }
+}
+
+pub fn timeline(events: &[AuditEventRow]) -> Markup {
+    html! {
+        ul ."timeline"."timeline-vertical"."timeline-compact" {
+            @for (idx, event) in events.iter().enumerate() {
+                li {
+                    @if idx > 0 { hr; }
+                    ."timeline-middle" {(icons::clock())}
+                    ."timeline-end"."timeline-box"."w-full" {
+                        ."flex"."flex-wrap"."gap-2"."items-center" {
+                            ."badge"."badge-outline" {(event.action)}
+                            span ."text-sm"."opacity-70" {
+                                (components::format_timestamp(&event.created_at))" by "(event.account_email.as_deref().unwrap_or("System"))
+                            }
+                        }
+                        @if let Some(reason) = &event.reason { p {(reason)} }
+                        (event_changes(event))
+                    }
+                    @if idx + 1 < events.len() { hr; }
+                }
+            }
+        }
+    }
+}
+
+pub async fn activity_list(
+    nest: NestedPath,
+    Query(params): Query<AuditEventsQuery>,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    let actions = sqlx::query_scalar!("SELECT DISTINCT action FROM audit_events ORDER BY action")
+        .fetch_all(&state.db_pool)
+        .await
+        .unwrap_or_default();
+    let accounts = sqlx::query_as!(
+        SelectIdOption,
+        r#"SELECT id, email AS "description!" FROM accounts ORDER BY email"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
+
+    html! { #"activity_list" ."w-full"."max-w-4xl"."mx-auto" {
+        #"activity_search" ."card"."bg-base-200"."w-full"."border"."border-secondary" {
+            form hx-get={(nest.as_str())"/search"} hx-target="#activity_search_results" hx-push-url="true" ."card-body" {
+                ."card-title" {"Activity Log"}
+                label ."input"."input-bordered"."flex"."items-center"."gap-2" {
+                    input type="text" name="member_search" placeholder="Search by Member" value=[&params.member_search] ."grow"."bg-inherit";
+                    span ."text-secondary" {(icons::search())}
+                }
+                ."form-control" {
+                    label ."label"."cursor-pointer" {
+                        span ."label-text" {"Action"}
+                        select name="action" ."select"."select-bordered" {
+                            option value="" {"(Any Action)"}
+                            @for action in actions {
+                                option value=(action) selected[params.action.as_ref() == Some(&action)] {(action)}
+                            }
+                        }
+                    }
+                }
+                ."form-control" {
+                    label ."label"."cursor-pointer" {
+                        span ."label-text" {"Admin"}
+                        select name="account_id" ."select"."select-bordered" {
+                            option value="" {"(Anyone)"}
+                            @for account in accounts {
+                                option value=(account.id) selected[params.account_id == Some(account.id)] {(account.description)}
+                            }
+                        }
+                    }
+                }
+                ."form-control" {
+                    label ."label"."cursor-pointer" {
+                        span ."label-text" {"From"}
+                        input type="date" name="from" value=[params.from] ."input"."input-bordered";
+                    }
+                }
+                ."form-control" {
+                    label ."label"."cursor-pointer" {
+                        span ."label-text" {"To"}
+                        input type="date" name="to" value=[params.to] ."input"."input-bordered";
+                    }
+                }
+                ."card-actions"."justify-center" {
+                    button ."btn"."btn-primary"."w-1/2"."block"."mx-auto"."!mb-0" {"SEARCH"}
+                }
+            }
+        }
+        ."divider" {}
+        #"activity_search_results" hx-get={(nest.as_str())"/search"} hx-trigger="load" hx-vals=(serde_json::to_string(&params).unwrap()) {}
+    } }
+}
+
+pub async fn activity_results(
+    headers: HeaderMap,
+    nest: NestedPath,
+    Query(params): Query<AuditEventsQuery>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    if headers.contains_key("X-Rebuild-Page") {
+        return Ok(activity_list(nest, Query(params), State(state)).await);
+    }
+
+    let (events, total) = try_join!(
+        crate::db::audit_events::search(&params, &state),
+        crate::db::audit_events::count(&params, &state)
+    )
+    .map_err_response(ErrorResponse::InternalServerError)?;
+
+    let pagebtn = |request_opt: Option<PaginationRequest>, text: &str| -> Markup {
+        html! {
+            @if let Some(request) = request_opt {
+                button ."btn"."btn-outline"."join-item"."w-1/4" hx-get={(nest.as_str())"/search"} hx-target="#activity_search_results"
+                    hx-vals=(serde_json::to_string(&AuditEventsQuery {count: request.count, offset: request.offset, ..params.clone()}).unwrap()) {(text)}
+            }
+            @else { button ."btn"."btn-outline"."join-item"."w-1/4" disabled {(text)}}
+        }
+    };
+
+    let prev = match params.offset {
+        0 => None,
+        _ => Some(PaginationRequest {
+            count: params.count,
+            offset: params.offset.saturating_sub(params.count),
+        }),
+    };
+    let next = if params.offset + params.count >= total {
+        None
+    } else {
+        Some(PaginationRequest {
+            count: params.count,
+            offset: params.offset + params.count,
+        })
+    };
+
+    Ok(html! {
+        ."overflow-x-auto" { table ."table"."table-zebra"."table-auto" {
+            thead { tr {
+                th {"When"}
+                th {"Admin"}
+                th {"Member"}
+                th {"Action"}
+                th {"Details"}
+            }}
+            @for event in &events {
+                tr {
+                    td ."whitespace-nowrap" {(components::format_timestamp(&event.created_at))}
+                    td {(event.account_email.as_deref().unwrap_or("System"))}
+                    td {
+                        @if let (Some(name), Some(email)) = (&event.member_name, &event.member_email) {
+                            a href={"/admin/members?search="(email)} target="_blank" ."btn"."btn-link" {(name)}
+                        }
+                    }
+                    td { ."badge"."badge-outline"."whitespace-nowrap" {(event.action)} }
+                    td {
+                        @if let Some(reason) = &event.reason { p {(reason)} }
+                        (event_changes(event))
+                    }
+                }
+            }
+        }}
+        ."divider" {}
+        #"activity_pagination" ."join"."join-vertical"."md:join-horizontal"."justify-center"."w-full"."items-center" {
+            (pagebtn(prev, "Previous"))
+            ."btn"."btn-outline"."join-item"."w-1/4"."!text-neutral-content" disabled {
+                (params.offset + 1)" - "(params.offset + (events.len() as u64))" of "(total)
+            }
+            (pagebtn(next, "Next"))
+        }
+    })
+}
+
+pub fn router(state: crate::AppState) -> Router {
+    Router::new()
+        .route("/", get(activity_list))
+        .route("/search", get(activity_results))
+        .with_state(state.clone())
+}
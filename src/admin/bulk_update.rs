@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use crate::{
+    db::audit_events::AuditEvent,
     err_responses::{ErrorResponse, MapErrorResponse},
     icons,
 };
@@ -154,10 +155,12 @@ pub async fn submit_givingfuel_bulk_update(
     let mut csv_reader = csv::Reader::from_reader(csv_text.as_bytes());
 
     let mut emails = HashSet::<String>::from_iter(
-        sqlx::query_scalar!(r#"SELECT email FROM members"#)
-            .fetch_all(&state.db_pool)
-            .await
-            .unwrap(),
+        sqlx::query_scalar!(
+            r#"SELECT email AS "email!" FROM members UNION SELECT email FROM member_email_aliases"#
+        )
+        .fetch_all(&state.db_pool)
+        .await
+        .unwrap(),
     );
 
     let mut transaction = state.db_pool.begin().await.unwrap();
@@ -196,10 +199,10 @@ pub async fn submit_givingfuel_bulk_update(
         }
 
         sqlx::query!(
-            r#"INSERT INTO payments (member_id, effective_on, amount_paid, payment_method, transaction_id)
-                SELECT               id,        $2,           $3,          'webconnex',    $4
+            r#"INSERT INTO payments (member_id, effective_on, amount_paid, duration_months,         plan_id,             payment_method, transaction_id)
+                SELECT               id,        $2,           $3,          duration_for_amount($3), plan_for_amount($3), 'webconnex',    $4
                 FROM members
-                WHERE email = $1"#,
+                WHERE id = member_id_by_email($1)"#,
             row.email,
             row.payment_date.date(),
             row.total.unwrap(),
@@ -212,6 +215,19 @@ pub async fn submit_givingfuel_bulk_update(
         payments_added += 1;
     }
 
+    AuditEvent {
+        account_id: Some(user.account.id),
+        action: "bulk_import.givingfuel",
+        after: Some(serde_json::json!({
+            "members_added": members_added,
+            "payments_added": payments_added,
+        })),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+
     transaction
         .commit()
         .await
@@ -311,6 +327,21 @@ pub async fn submit_donorbox_bulk_update(
         page += 1;
     }
 
+    AuditEvent {
+        account_id: Some(user.account.id),
+        action: "bulk_import.donorbox",
+        reason: start_date.as_deref(),
+        after: Some(serde_json::json!({
+            "members_added": new_members,
+            "payments_added": transactions,
+            "errors": errors.len(),
+        })),
+        ..Default::default()
+    }
+    .record(&state.db_pool)
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+
     let resp = if errors.is_empty() {
         format!(
             "Added {} members and {} payments successfully",
@@ -0,0 +1,643 @@
+use axum::{
+    extract::{NestedPath, Path, Query, State},
+    response::Response,
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use maud::{html, Markup};
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+use time::OffsetDateTime;
+
+use crate::{
+    components,
+    db::{
+        audit_events::AuditEvent,
+        members::{recipients, MembersQuery},
+    },
+    err_responses::{ErrorResponse, MapErrorResponse},
+    icons,
+    send_email::{
+        build_message, get_email_sender, get_email_template, list_email_templates, outbox,
+        sanitize_email, validate_email_template, EmailTemplate, EmailValues,
+        BUILTIN_EMAIL_TEMPLATES,
+    },
+};
+
+struct SelectIdOption {
+    id: i32,
+    description: String,
+}
+
+struct CampaignRow {
+    id: i32,
+    account_email: Option<String>,
+    email_key: String,
+    subject: String,
+    segment: serde_json::Value,
+    created_at: OffsetDateTime,
+    recipients: i64,
+    sent: i64,
+    queued: i64,
+    failed: i64,
+}
+
+async fn campaign_rows(
+    id: Option<i32>,
+    db_pool: &sqlx::PgPool,
+) -> Result<Vec<CampaignRow>, sqlx::Error> {
+    sqlx::query_as!(
+        CampaignRow,
+        r#"SELECT
+                email_campaigns.id,
+                accounts.email AS "account_email?",
+                email_campaigns.email_key,
+                email_campaigns.subject,
+                email_campaigns.segment,
+                email_campaigns.created_at,
+                COUNT(email_outbox.id) AS "recipients!",
+                COUNT(email_outbox.id) FILTER (WHERE email_outbox.status = 'sent') AS "sent!",
+                COUNT(email_outbox.id) FILTER (WHERE email_outbox.status = 'queued') AS "queued!",
+                COUNT(email_outbox.id) FILTER (WHERE email_outbox.status = 'failed') AS "failed!"
+            FROM email_campaigns
+                LEFT JOIN accounts ON accounts.id = email_campaigns.account_id
+                LEFT JOIN email_outbox ON email_outbox.campaign_id = email_campaigns.id
+            WHERE $1::INT IS NULL OR email_campaigns.id = $1
+            GROUP BY email_campaigns.id, accounts.email
+            ORDER BY email_campaigns.id DESC
+            LIMIT 100"#,
+        id
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+struct RecipientRow {
+    member_id: Option<i32>,
+    member_name: Option<String>,
+    to_address: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    sent_at: Option<OffsetDateTime>,
+}
+
+async fn recipient_rows(
+    campaign_id: i32,
+    db_pool: &sqlx::PgPool,
+) -> Result<Vec<RecipientRow>, sqlx::Error> {
+    sqlx::query_as!(
+        RecipientRow,
+        r#"SELECT
+                member_id,
+                members.first_name || ' ' || members.last_name AS "member_name?",
+                to_address,
+                status,
+                attempts,
+                last_error,
+                sent_at
+            FROM email_outbox
+                LEFT JOIN members ON members.id = member_id
+            WHERE campaign_id = $1
+            ORDER BY
+                CASE status WHEN 'failed' THEN 0 WHEN 'queued' THEN 1 ELSE 2 END,
+                email_outbox.id"#,
+        campaign_id
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+async fn describe_segment(segment: &MembersQuery, db_pool: &sqlx::PgPool) -> String {
+    let mut filters = Vec::new();
+    if let Some(search) = segment
+        .search
+        .as_deref()
+        .filter(|search| !search.is_empty())
+    {
+        filters.push(format!("matching \"{}\"", search));
+    }
+    match segment.member_status {
+        Some(true) => filters.push(String::from("active")),
+        Some(false) => filters.push(String::from("inactive")),
+        None => {}
+    }
+    match segment.discord_status {
+        Some(true) => filters.push(String::from("registered on Discord")),
+        Some(false) => filters.push(String::from("not registered on Discord")),
+        None => {}
+    }
+    if segment.generation_id >= 0 {
+        let title = sqlx::query_scalar!(
+            "SELECT title FROM generations WHERE id = $1",
+            segment.generation_id
+        )
+        .fetch_optional(db_pool)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| format!("#{}", segment.generation_id));
+        filters.push(format!("in the {} generation", title));
+    }
+    if segment.plan_id >= 0 {
+        let name = sqlx::query_scalar!("SELECT name FROM plans WHERE id = $1", segment.plan_id)
+            .fetch_optional(db_pool)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| format!("#{}", segment.plan_id));
+        filters.push(format!("on the {} plan", name));
+    }
+    if filters.is_empty() {
+        String::from("All members")
+    } else {
+        format!("Members {}", filters.join(", "))
+    }
+}
+
+fn progress(campaign: &CampaignRow) -> Markup {
+    html! {
+        ."flex"."items-center"."gap-2" {
+            progress ."progress"."progress-success"."w-32" value=(campaign.sent) max=(campaign.recipients.max(1)) {}
+            span ."text-sm"."whitespace-nowrap" {(campaign.sent)" / "(campaign.recipients)" sent"}
+            @if campaign.failed > 0 {
+                ."badge"."badge-error" {(campaign.failed)" failed"}
+            }
+        }
+    }
+}
+
+async fn campaigns_list(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let campaigns = campaign_rows(None, &state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
+
+    Ok(html! { #"campaigns_list" ."w-full"."max-w-6xl"."mx-auto" {
+        button ."btn"."btn-primary"."block"."mx-auto"
+            hx-get={(nest.as_str())"/new"} hx-target="main" hx-push-url="true" {"NEW CAMPAIGN"}
+        ."divider" {}
+        @if campaigns.is_empty() {
+            p ."text-center"."opacity-70" {"No campaigns yet."}
+        } @else {
+            ."overflow-x-auto" { table ."table"."table-zebra"."table-auto" {
+                thead { tr {
+                    th {"Created"}
+                    th {"Subject"}
+                    th {"Sent By"}
+                    th {"Progress"}
+                }}
+                tbody {
+                    @for campaign in &campaigns {
+                        tr ."hover"."cursor-pointer" hx-get={(nest.as_str())"/"(campaign.id)} hx-target="main" hx-push-url="true" {
+                            td ."whitespace-nowrap" {(components::format_timestamp(&campaign.created_at))}
+                            td {
+                                (campaign.subject)
+                                ."text-xs"."opacity-60" {(campaign.email_key)}
+                            }
+                            td {(campaign.account_email.as_deref().unwrap_or_default())}
+                            td {(progress(campaign))}
+                        }
+                    }
+                }
+            }}
+        }
+    } })
+}
+
+async fn campaign_details(
+    nest: NestedPath,
+    Path(campaign_id): Path<i32>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let campaign = campaign_rows(Some(campaign_id), &state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)
+        .map_err_response(ErrorResponse::StatusCode(reqwest::StatusCode::NOT_FOUND))?;
+    let recipients = recipient_rows(campaign_id, &state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
+    let segment = match serde_json::from_value::<MembersQuery>(campaign.segment.clone()) {
+        Ok(segment) => describe_segment(&segment, &state.db_pool).await,
+        Err(_) => String::from("Unknown"),
+    };
+
+    Ok(html! {
+        #"campaign_details" ."w-full"."max-w-6xl"."mx-auto"
+            hx-get=[(campaign.queued > 0).then(|| format!("{}/{}", nest.as_str(), campaign.id))]
+            hx-trigger="every 5s" hx-swap="outerHTML" {
+            ."card"."bg-base-200" { ."card-body" {
+                h2 ."card-title" {(campaign.subject)}
+                p ."text-sm" {
+                    "Template "span ."font-mono" {(campaign.email_key)}
+                    " sent "(components::format_timestamp(&campaign.created_at))
+                    @if let Some(account_email) = &campaign.account_email { " by "(account_email) }
+                }
+                p ."text-sm" {(segment)}
+                (progress(&campaign))
+                @if campaign.queued > 0 {
+                    p ."text-sm"."opacity-70" {(campaign.queued)" still queued, delivering via "(state.mail_transport.description())}
+                }
+                @if campaign.failed > 0 {
+                    ."card-actions"."justify-end" {
+                        button ."btn"."btn-sm"."btn-outline"."btn-secondary"
+                            hx-post={(nest.as_str())"/"(campaign.id)"/retry"} hx-target="#campaign_details" hx-swap="outerHTML"
+                            hx-confirm={"Queue the "(campaign.failed)" failed email(s) again?"} {"RETRY FAILED"}
+                    }
+                }
+            }}
+            ."overflow-x-auto"."mt-4" { table ."table"."table-zebra"."table-auto" {
+                thead { tr {
+                    th {"To"}
+                    th {"Status"}
+                    th {"Delivery"}
+                }}
+                tbody {
+                    @for recipient in &recipients {
+                        tr {
+                            td {
+                                (recipient.to_address)
+                                @if let (Some(member_id), Some(member_name)) = (recipient.member_id, &recipient.member_name) {
+                                    ."text-xs"."opacity-60" {(member_name)" (#"(member_id)")"}
+                                }
+                            }
+                            td {
+                                @match recipient.status.as_str() {
+                                    "sent" => ."badge"."badge-success" {"Sent"},
+                                    "failed" => ."badge"."badge-error" {"Failed"},
+                                    _ => ."badge"."badge-warning" {"Queued"},
+                                }
+                            }
+                            td ."text-sm" {
+                                (recipient.attempts)" attempt(s)"
+                                @if let Some(sent_at) = &recipient.sent_at {
+                                    ."text-xs"."opacity-60" {"Sent "(components::format_timestamp(sent_at))}
+                                }
+                                @if let Some(last_error) = &recipient.last_error {
+                                    ."text-xs"."text-error"."max-w-xs"."break-words" {(last_error)}
+                                }
+                            }
+                        }
+                    }
+                }
+            }}
+        }
+    })
+}
+
+async fn new_campaign_form(
+    nest: NestedPath,
+    Query(params): Query<MembersQuery>,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    let generation_options = sqlx::query_as!(
+        SelectIdOption,
+        r#"SELECT id, CONCAT(title, ' (', start_date, ')') AS "description!" FROM generations"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
+    let plan_options = sqlx::query_as!(
+        SelectIdOption,
+        r#"SELECT id, name AS "description!" FROM plans ORDER BY archived, name"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
+    // Built-in templates expect values like an invite link that campaigns don't fill in
+    let email_templates = list_email_templates(&state.db_pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|email_template| !BUILTIN_EMAIL_TEMPLATES.contains(&email_template.id.as_str()))
+        .collect::<Vec<_>>();
+
+    html! { #"new_campaign" ."w-full"."max-w-4xl"."mx-auto" {
+        #"campaign_results" {}
+        form #"campaign_form" hx-post=(nest.as_str()) hx-target="#campaign_results"
+            hx-confirm="Send this email to every member in the segment?" hx-disinherit="*" ."*:my-3" {
+            ."divider" {"Recipients"}
+            ."grid"."grid-cols-1"."md:grid-cols-2"."gap-2" {
+                label ."input"."input-bordered"."flex"."items-center"."gap-2"."md:col-span-2" {
+                    input type="text" name="search" placeholder="Name or email contains" value=[&params.search] ."grow"."bg-inherit";
+                    span ."text-secondary" {(icons::search())}
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Active Status"} }
+                    select name="member_status" ."select"."select-bordered" {
+                        option value="" selected[params.member_status.is_none()] {"(Ignore)"}
+                        option value="true" selected[params.member_status==Some(true)] {"Active"}
+                        option value="false" selected[params.member_status==Some(false)] {"Inactive"}
+                    }
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Discord Status"} }
+                    select name="discord_status" ."select"."select-bordered" {
+                        option value="" selected[params.discord_status.is_none()] {"(Ignore)"}
+                        option value="true" selected[params.discord_status==Some(true)] {"Registered"}
+                        option value="false" selected[params.discord_status==Some(false)] {"Unregistered"}
+                    }
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Generation"} }
+                    select name="generation_id" ."select"."select-bordered" {
+                        option value="-1" {"(Any Generation)"}
+                        @for gen in generation_options {
+                            option value=(gen.id) selected[gen.id==params.generation_id] {(gen.description)}
+                        }
+                    }
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Current Plan"} }
+                    select name="plan_id" ."select"."select-bordered" {
+                        option value="-1" {"(Any Plan)"}
+                        @for plan in plan_options {
+                            option value=(plan.id) selected[plan.id==params.plan_id] {(plan.description)}
+                        }
+                    }
+                }
+            }
+            #"campaign_recipients" hx-post={(nest.as_str())"/recipients"} hx-include="#campaign_form" hx-target="this"
+                hx-trigger="load, change from:#campaign_form, keyup changed delay:500ms from:#campaign_form" {}
+            ."divider" {"Message"}
+            label ."form-control" {
+                ."label" { span ."label-text" {"Template"} }
+                select name="email_key" ."select"."select-bordered"
+                    onchange="document.getElementById('campaign_custom_message').classList.toggle('hidden', this.value !== '')" {
+                    option value="" {"(Write a new message)"}
+                    @for email_template in &email_templates {
+                        option value=(email_template.id) {(email_template.id)" - "(email_template.subject)}
+                    }
+                }
+            }
+            #"campaign_custom_message" ."*:my-2" {
+                input type="text" name="subject" placeholder="Subject" ."input"."input-bordered"."w-full";
+                textarea name="template" placeholder="HTML body, e.g. <p>Hi {first_name},</p>"
+                    ."textarea"."textarea-primary"."font-mono"."w-full"."min-h-60" {}
+                textarea name="plaintext" placeholder="Plain text body (optional)"
+                    ."textarea"."textarea-bordered"."font-mono"."w-full"."min-h-24" {}
+                p ."text-xs"."opacity-70" {"Each email is personalized with {first_name}, {last_name} and {email}."}
+            }
+            button ."btn"."btn-primary"."w-1/2"."block"."mx-auto"."!mb-0" {"SEND CAMPAIGN"}
+        }
+    } }
+}
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+pub struct CampaignForm {
+    #[serde(default)]
+    search: String,
+    #[serde(default)]
+    member_status: String,
+    #[serde(default)]
+    discord_status: String,
+    #[serde_inline_default(-1)]
+    generation_id: i32,
+    #[serde_inline_default(-1)]
+    plan_id: i32,
+    #[serde(default)]
+    email_key: String,
+    #[serde(default)]
+    subject: String,
+    #[serde(default)]
+    template: String,
+    #[serde(default)]
+    plaintext: String,
+}
+
+impl CampaignForm {
+    fn segment(&self) -> MembersQuery {
+        MembersQuery {
+            search: Some(self.search.trim().to_owned()).filter(|search| !search.is_empty()),
+            discord: None,
+            member_status: self.member_status.parse().ok(),
+            discord_status: self.discord_status.parse().ok(),
+            count: 0,
+            offset: 0,
+            generation_id: self.generation_id,
+            plan_id: self.plan_id,
+            sort_by: String::from("lastname"),
+            sort_desc: false,
+        }
+    }
+
+    async fn email_template(&self, db_pool: &sqlx::PgPool) -> Result<EmailTemplate, String> {
+        if BUILTIN_EMAIL_TEMPLATES.contains(&self.email_key.as_str()) {
+            return Err(format!(
+                "The {} template is sent automatically and can't be used for a campaign",
+                self.email_key
+            ));
+        }
+        if !self.email_key.is_empty() {
+            return get_email_template(&self.email_key, db_pool)
+                .await
+                .map_err(|err| err.to_string());
+        }
+        let email_template = EmailTemplate {
+            id: String::from("campaign"),
+            subject: self.subject.trim().to_owned(),
+            description: String::new(),
+            template: sanitize_email(&self.template),
+            plaintext: Some(self.plaintext.trim().to_owned())
+                .filter(|plaintext| !plaintext.is_empty()),
+        };
+        if email_template.subject.is_empty() {
+            return Err(String::from("Subject is required"));
+        }
+        validate_email_template(&email_template.template)
+            .map_err(|err| format!("HTML body: {}", err))?;
+        if let Some(plaintext) = &email_template.plaintext {
+            validate_email_template(plaintext)
+                .map_err(|err| format!("Plain text body: {}", err))?;
+        }
+        Ok(email_template)
+    }
+}
+
+async fn preview_recipients(
+    State(state): State<crate::AppState>,
+    Form(form): Form<CampaignForm>,
+) -> Result<Markup, Response> {
+    let segment = form.segment();
+    let members = recipients(&segment, &state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(html! {
+        ."alert".alert-info[!members.is_empty()].alert-warning[members.is_empty()] {
+            span {
+                b {(members.len())" recipient(s)"}
+                " - "(describe_segment(&segment, &state.db_pool).await)", excluding banned members"
+                @if !members.is_empty() {
+                    ."text-xs"."opacity-70" {
+                        @for member in members.iter().take(5) {
+                            (member.first_name)" "(member.last_name)"; "
+                        }
+                        @if members.len() > 5 { "and "(members.len() - 5)" more" }
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn create_campaign(
+    form: &CampaignForm,
+    state: &crate::AppState,
+    admin: &crate::auth::Jwt,
+) -> Result<(i32, Vec<String>), String> {
+    let segment = form.segment();
+    let email_template = form.email_template(&state.db_pool).await?;
+    let sender = get_email_sender(&state.db_pool).await?;
+    let members = recipients(&segment, &state.db_pool)
+        .await
+        .map_err(|err| err.to_string())?;
+    if members.is_empty() {
+        return Err(String::from("No members match these filters"));
+    }
+
+    let mut transaction = state.db_pool.begin().await.map_err(|err| err.to_string())?;
+    let campaign_id = sqlx::query_scalar!(
+        r#"INSERT INTO email_campaigns (account_id, email_key, subject, template, plaintext, segment)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id"#,
+        admin.account.id,
+        email_template.id,
+        email_template.subject,
+        email_template.template,
+        email_template.plaintext,
+        serde_json::json!(segment)
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    let mut skipped = Vec::new();
+    for member in &members {
+        let values = EmailValues {
+            first_name: member.first_name.clone(),
+            last_name: member.last_name.clone(),
+            email: member.email.clone(),
+            ..Default::default()
+        };
+        let message = match build_message(&email_template, &sender, &member.email, &values) {
+            Ok(message) => message,
+            Err(err) => {
+                tracing::warn!(
+                    "Skipping {} in campaign {}: {}",
+                    member.email,
+                    campaign_id,
+                    err
+                );
+                skipped.push(member.email.clone());
+                continue;
+            }
+        };
+        outbox::enqueue(
+            outbox::OutboxMessage {
+                email_key: &email_template.id,
+                subject: &email_template.subject,
+                to_address: &member.email,
+                member_id: Some(member.id),
+                campaign_id: Some(campaign_id),
+                message,
+            },
+            &mut *transaction,
+        )
+        .await
+        .map_err(|err| err.to_string())?;
+    }
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        action: "email.campaign",
+        reason: Some(&email_template.subject),
+        after: Some(serde_json::json!({
+            "campaign": campaign_id,
+            "email_key": email_template.id,
+            "segment": segment,
+            "recipients": members.len() - skipped.len(),
+            "skipped": skipped,
+        })),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    transaction.commit().await.map_err(|err| err.to_string())?;
+    Ok((campaign_id, skipped))
+}
+
+async fn send_campaign(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<CampaignForm>,
+) -> Markup {
+    match create_campaign(&form, &state, &admin).await {
+        Ok((campaign_id, skipped)) => html! {
+            ."alert"."alert-success" {
+                (icons::success())
+                span {
+                    "Queued campaign #"(campaign_id)
+                    @if !skipped.is_empty() { ", skipped "(skipped.join(", ")) }
+                }
+            }
+            div hx-get={(nest.as_str())"/"(campaign_id)} hx-trigger="load delay:1s" hx-target="main" hx-push-url="true" {}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    }
+}
+
+async fn retry_failed(
+    nest: NestedPath,
+    Path(campaign_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+) -> Result<Markup, Response> {
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+    let requeued = outbox::resend_failed_campaign(campaign_id, &mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        action: "email.campaign_retry",
+        after: Some(serde_json::json!({ "campaign": campaign_id, "requeued": requeued })),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    let details = campaign_details(nest, Path(campaign_id), State(state)).await?;
+    Ok(html! {
+        (details)
+        (components::ToastAlert::Success(&format!("Queued {} email(s) again", requeued)))
+    })
+}
+
+pub fn router(state: crate::AppState) -> Router {
+    Router::new()
+        .route("/", get(campaigns_list).post(send_campaign))
+        .route("/new", get(new_campaign_form))
+        .route("/recipients", post(preview_recipients))
+        .route("/{campaign_id}", get(campaign_details))
+        .route("/{campaign_id}/retry", post(retry_failed))
+        .with_state(state.clone())
+}
@@ -0,0 +1,133 @@
+use axum::{
+    extract::{NestedPath, Path, State},
+    Extension, Form,
+};
+use maud::{html, Markup};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::{db::audit_events::AuditEvent, icons};
+
+struct AccountDiscordRow {
+    id: i32,
+    email: String,
+    discord: Option<Decimal>,
+}
+
+async fn accounts_list(nest: &str, state: &crate::AppState, alert: Option<Markup>) -> Markup {
+    let accounts = sqlx::query_as!(
+        AccountDiscordRow,
+        r#"SELECT accounts.id, accounts.email, account_discord_ids.discord AS "discord?"
+            FROM accounts
+                LEFT JOIN account_discord_ids ON account_discord_ids.account_id = accounts.id
+            WHERE accounts.is_admin
+            ORDER BY accounts.email"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
+
+    html! {
+        #"discord_accounts_config" {
+            @if let Some(alert) = alert { (alert) }
+            p ."text-sm"."mb-2" {
+                "Admin commands run in Discord are recorded as the linked account. Discord users without a linked admin account cannot use them."
+            }
+            table ."table" {
+                thead { tr { th {"Account"} th {"Discord User ID"} th {} } }
+                tbody {
+                    @for account in &accounts {
+                        tr {
+                            td {(account.email)}
+                            td colspan="2" {
+                                form ."flex"."gap-2" hx-post={(nest)"/discord_accounts/"(account.id)} hx-target="#discord_accounts_config" hx-swap="outerHTML" {
+                                    input type="text" name="discord" inputmode="numeric" pattern="[0-9]*" placeholder="Not linked"
+                                        value=[account.discord] ."input"."input-bordered"."input-sm"."w-full";
+                                    button ."btn"."btn-sm"."btn-primary" {"SAVE"}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub async fn discord_accounts_form(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    accounts_list(nest.as_str(), &state, None).await
+}
+
+#[derive(Deserialize)]
+pub struct DiscordAccountFormData {
+    #[serde(default)]
+    discord: String,
+}
+
+pub async fn set_discord_account(
+    nest: NestedPath,
+    Path(account_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<DiscordAccountFormData>,
+) -> Markup {
+    let discord = match form.discord.trim() {
+        "" => None,
+        discord => match discord.parse::<u64>() {
+            Ok(discord) => Some(Decimal::from(discord)),
+            Err(_) => {
+                let alert = html! {
+                    ."alert"."alert-error" {(icons::error()) span {"Invalid Discord user ID"}}
+                };
+                return accounts_list(nest.as_str(), &state, Some(alert)).await;
+            }
+        },
+    };
+
+    let result = async {
+        let mut transaction = state.db_pool.begin().await?;
+        let before = sqlx::query_scalar!(
+            "DELETE FROM account_discord_ids WHERE account_id = $1 RETURNING discord",
+            account_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        if let Some(discord) = discord {
+            sqlx::query!(
+                "INSERT INTO account_discord_ids (discord, account_id) VALUES ($1, $2)",
+                discord,
+                account_id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        AuditEvent {
+            account_id: Some(admin.account.id),
+            action: "config.account_discord",
+            before: Some(serde_json::json!({ "account_id": account_id, "discord": before })),
+            after: Some(serde_json::json!({ "account_id": account_id, "discord": discord })),
+            ..Default::default()
+        }
+        .record(&mut *transaction)
+        .await?;
+
+        transaction.commit().await
+    };
+
+    let alert = match result.await {
+        Ok(_) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Successfully updated Discord account!"}}
+        },
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => html! {
+            ."alert"."alert-error" {(icons::error()) span {"That Discord user is already linked to another account"}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    };
+    accounts_list(nest.as_str(), &state, Some(alert)).await
+}
@@ -0,0 +1,62 @@
+use axum::extract::State;
+use maud::{html, Markup};
+
+use crate::{
+    discord::bans::{ban_report, BanMismatch},
+    icons,
+};
+
+fn mismatch_table(mismatches: &[BanMismatch]) -> Markup {
+    html! {
+        table ."table"."table-sm" {
+            thead { tr { th {"Discord User"} th {"Member"} th {"Discord Ban Reason"} } }
+            tbody {
+                @for mismatch in mismatches {
+                    tr {
+                        td {
+                            (mismatch.username.as_deref().unwrap_or(""))
+                            ."text-xs"."opacity-60" {(mismatch.user_id)}
+                        }
+                        td {
+                            @if let Some(member) = &mismatch.member {
+                                a href={"/admin/members?search="(member.email)} target="_blank" ."btn"."btn-link" {
+                                    (member.last_name)", "(member.first_name)
+                                }
+                                @if member.banned { ."badge"."badge-error" {"Banned"} }
+                            } @else {
+                                span ."opacity-60" {"Not registered"}
+                            }
+                        }
+                        td {(mismatch.ban_reason.as_deref().unwrap_or(""))}
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub async fn discord_bans_report(State(state): State<crate::AppState>) -> Markup {
+    let report = match ban_report(&state).await {
+        Ok(report) => report,
+        Err(err) => {
+            return html! {
+                ."alert"."alert-error" {(icons::error()) span {(err)}}
+            }
+        }
+    };
+
+    html! {
+        ."divider" {"Banned in Discord, not in MDMA"}
+        @if report.discord_only.is_empty() {
+            ."alert"."alert-success" {(icons::success()) span {"Every Discord ban matches an MDMA ban."}}
+        } @else {
+            (mismatch_table(&report.discord_only))
+        }
+        ."divider" {"Banned in MDMA, not in Discord"}
+        @if report.mdma_only.is_empty() {
+            ."alert"."alert-success" {(icons::success()) span {"Every banned member with a linked Discord account is banned in Discord."}}
+        } @else {
+            (mismatch_table(&report.mdma_only))
+        }
+    }
+}
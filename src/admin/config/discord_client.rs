@@ -0,0 +1,32 @@
+use axum::extract::State;
+use maud::{html, Markup};
+
+use crate::icons;
+
+pub async fn discord_client_status(State(state): State<crate::AppState>) -> Markup {
+    if !state.discord.enabled() {
+        return html! {
+            ."alert"."alert-warning" {
+                (icons::warning())
+                span {"Discord support is disabled. Set the DISCORD_BOT_TOKEN, DISCORD_APPLICATION_ID and DISCORD_GUILD_ID secrets, or DISCORD_CLIENT=mock for local development."}
+            }
+        };
+    }
+
+    let calls = state.discord.recorded_calls();
+    html! {
+        ."alert"."alert-success" {
+            (icons::success())
+            span {
+                "Connected to guild "(state.discord.guild_id())". "
+                @if state.discord_verifier.is_none() { "Slash commands are unavailable until DISCORD_API_KEY is set." }
+            }
+        }
+        @if !calls.is_empty() {
+            ."divider" {"Recorded Calls"}
+            ul ."font-mono"."text-xs"."max-h-96"."overflow-y-auto" {
+                @for call in calls.iter().rev() { li {(call)} }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use axum::{
+    extract::{NestedPath, State},
+    Extension,
+};
+use axum_extra::extract::Form;
+use maud::{html, Markup};
+use serde::Deserialize;
+use serenity::model::channel::ChannelType;
+
+use crate::{
+    db::audit_events::AuditEvent,
+    discord::{
+        insert_discord_setting,
+        notifications::{event_enabled, notification_channel, setting_key, EVENTS},
+    },
+    icons,
+};
+
+async fn settings_snapshot(
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT COALESCE(jsonb_object_agg(id, value), '{}') AS "settings!"
+            FROM discord_settings
+            WHERE id = 'notification_channel' OR id LIKE 'notify:%'"#
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn discord_notifications_form(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    let selected_channel = notification_channel(&state.db_pool).await;
+    let mut channels = match state.discord.get_channels().await {
+        Ok(channels) => channels,
+        Err(err) => {
+            return html! {
+                ."alert"."alert-error" {(icons::error()) span {"Could not load Discord channels: "(err)}}
+            }
+        }
+    };
+    channels.retain(|channel| channel.kind == ChannelType::Text);
+    channels.sort_by_key(|channel| channel.position);
+
+    let mut enabled = Vec::new();
+    for (event, label) in EVENTS {
+        enabled.push((*event, *label, event_enabled(event, &state.db_pool).await));
+    }
+
+    html! {
+        #"discord_notifications_results" {}
+        form hx-post={(nest.as_str())"/discord_notifications"} hx-target="#discord_notifications_results" {
+            label ."form-control"."w-full"."max-w-lg"."mx-auto" {
+                ."label" { span ."label-text" {"Notification Channel"} }
+                select name="channel" ."select"."select-bordered"."w-full" {
+                    option value="" selected[selected_channel.is_none()] {"(Disabled)"}
+                    @for channel in &channels {
+                        option value=(channel.id) selected[selected_channel == Some(channel.id)] {"#"(channel.name)}
+                    }
+                }
+                ."label" { span ."label-text-alt" {"Membership events from webhooks, bulk imports and this admin panel are posted here."} }
+            }
+            ."w-full"."max-w-lg"."mx-auto"."my-2" {
+                @for (event, label, checked) in &enabled {
+                    label ."label"."cursor-pointer" {
+                        span ."label-text" {(label)}
+                        input type="checkbox" name="events" value=(event) checked[*checked] ."toggle"."toggle-primary";
+                    }
+                }
+            }
+            button ."btn"."btn-primary"."w-1/2"."block"."mx-auto"."!mb-0"."mt-2" {"UPDATE"}
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DiscordNotificationsFormData {
+    channel: String,
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+pub async fn set_discord_notifications(
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<DiscordNotificationsFormData>,
+) -> Markup {
+    let result = async {
+        let mut transaction = state.db_pool.begin().await?;
+        let before = settings_snapshot(&mut *transaction).await?;
+
+        if form.channel.is_empty() {
+            sqlx::query!("DELETE FROM discord_settings WHERE id = 'notification_channel'")
+                .execute(&mut *transaction)
+                .await?;
+        } else {
+            insert_discord_setting("notification_channel", &form.channel, &mut *transaction)
+                .await?;
+        }
+        for (event, _) in EVENTS {
+            let enabled = form.events.iter().any(|enabled| enabled == event);
+            insert_discord_setting(&setting_key(event), &enabled.to_string(), &mut *transaction)
+                .await?;
+        }
+
+        let after = settings_snapshot(&mut *transaction).await?;
+        AuditEvent {
+            account_id: Some(admin.account.id),
+            action: "config.discord_notifications",
+            before: Some(before),
+            after: Some(after),
+            ..Default::default()
+        }
+        .record(&mut *transaction)
+        .await?;
+
+        transaction.commit().await
+    };
+
+    match result.await {
+        Ok(_) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Successfully updated Discord notifications!"}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{NestedPath, State},
+    Extension,
+};
+use axum_extra::extract::Form;
+use maud::{html, Markup};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Deserialize;
+use serenity::model::{guild::Role, id::RoleId};
+
+use crate::{
+    db::audit_events::AuditEvent,
+    discord::{get_discord_setting, insert_discord_setting, roles},
+    icons,
+};
+
+struct GenerationRoleRow {
+    id: i32,
+    title: String,
+    discord_role: Option<Decimal>,
+}
+
+async fn roles_snapshot(db: &mut sqlx::PgConnection) -> Result<serde_json::Value, sqlx::Error> {
+    let member_role = get_discord_setting("member_role", &mut *db).await?;
+    let generation_roles = sqlx::query_scalar!(
+        r#"SELECT COALESCE(jsonb_object_agg(title, discord_role), '{}') AS "roles!"
+            FROM generations
+            WHERE discord_role IS NOT NULL"#
+    )
+    .fetch_one(&mut *db)
+    .await?;
+    Ok(serde_json::json!({
+        "member_role": member_role,
+        "generation_roles": generation_roles,
+    }))
+}
+
+fn role_options(guild_roles: &[Role], selected: Option<RoleId>, state: &crate::AppState) -> Markup {
+    html! {
+        option value="" selected[selected.is_none()] {"(Disabled)"}
+        @for role in guild_roles {
+            @if role.id.get() != state.discord.guild_id().get() && !role.managed {
+                option value=(role.id) selected[selected == Some(role.id)] {(role.name)}
+            }
+        }
+    }
+}
+
+pub async fn discord_roles_form(nest: NestedPath, State(state): State<crate::AppState>) -> Markup {
+    let member_role = roles::member_role(&state.db_pool).await;
+    let guild_roles = match state.discord.get_guild_roles().await {
+        Ok(mut guild_roles) => {
+            guild_roles.sort_by_key(|role| std::cmp::Reverse(role.position));
+            guild_roles
+        }
+        Err(err) => {
+            return html! {
+                ."alert"."alert-error" {(icons::error()) span {"Could not load Discord roles: "(err)}}
+            }
+        }
+    };
+    let generations = match sqlx::query_as!(
+        GenerationRoleRow,
+        "SELECT id, title, discord_role FROM generations ORDER BY start_date"
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    {
+        Ok(generations) => generations,
+        Err(err) => {
+            return html! {
+                ."alert"."alert-error" {(icons::error()) span {(err)}}
+            }
+        }
+    };
+
+    html! {
+        #"discord_roles_results" {}
+        form hx-post={(nest.as_str())"/discord_roles"} hx-target="#discord_roles_results" {
+            label ."form-control"."w-full"."max-w-lg"."mx-auto" {
+                ."label" { span ."label-text" {"Member Role"} }
+                select name="member_role" ."select"."select-bordered"."w-full" {
+                    (role_options(&guild_roles, member_role, &state))
+                }
+                ."label" { span ."label-text-alt" {"Registered members get this role while their membership is active, and lose it when it lapses or they are cancelled or banned. Checked every hour."} }
+            }
+            ."divider" {"Generation Roles"}
+            @for generation in &generations {
+                label ."form-control"."w-full"."max-w-lg"."mx-auto" {
+                    ."label" { span ."label-text" {(generation.title)} }
+                    input type="hidden" name="generation_id" value=(generation.id);
+                    select name="generation_role" ."select"."select-bordered"."select-sm"."w-full" {
+                        (role_options(
+                            &guild_roles,
+                            generation.discord_role.and_then(|role| role.to_u64()).map(RoleId::new),
+                            &state,
+                        ))
+                    }
+                }
+            }
+            p ."text-xs"."opacity-70"."text-center"."my-2" {"Active members also get the role of their current generation, which moves to a later generation after a break in their membership."}
+            button ."btn"."btn-primary"."w-1/2"."block"."mx-auto"."!mb-0"."mt-2" {"UPDATE"}
+        }
+        ."divider" {"Pending Changes"}
+        ."flex"."justify-center"."gap-2" {
+            button ."btn"."btn-sm" hx-get={(nest.as_str())"/discord_roles/preview"} hx-target="#discord_roles_preview" {"DRY RUN"}
+            button ."btn"."btn-sm"."btn-warning" hx-post={(nest.as_str())"/discord_roles/apply"} hx-target="#discord_roles_preview"
+                hx-confirm="Update Discord roles now?" {"APPLY NOW"}
+        }
+        #"discord_roles_preview" ."mt-2" {}
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DiscordRolesFormData {
+    member_role: String,
+    #[serde(default)]
+    generation_id: Vec<i32>,
+    #[serde(default)]
+    generation_role: Vec<String>,
+}
+
+pub async fn set_discord_role(
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<DiscordRolesFormData>,
+) -> Markup {
+    let mut generation_roles = Vec::new();
+    for (generation_id, role) in form.generation_id.iter().zip(&form.generation_role) {
+        if role.is_empty() {
+            generation_roles.push((*generation_id, None));
+            continue;
+        }
+        match role.parse::<Decimal>() {
+            Ok(role) => generation_roles.push((*generation_id, Some(role))),
+            Err(err) => {
+                return html! {
+                    ."alert"."alert-error" {(icons::error()) span {(err)}}
+                }
+            }
+        }
+    }
+    let mut assigned = HashSet::new();
+    let all_unique = generation_roles
+        .iter()
+        .filter_map(|(_, role)| role.map(|role| role.to_string()))
+        .chain(Some(form.member_role.clone()).filter(|role| !role.is_empty()))
+        .all(|role| assigned.insert(role));
+    if !all_unique {
+        return html! {
+            ."alert"."alert-error" {(icons::error()) span {"Each Discord role can only be synced once."}}
+        };
+    }
+
+    let result = async {
+        let mut transaction = state.db_pool.begin().await?;
+        let before = roles_snapshot(&mut transaction).await?;
+
+        if form.member_role.is_empty() {
+            sqlx::query!("DELETE FROM discord_settings WHERE id = 'member_role'")
+                .execute(&mut *transaction)
+                .await?;
+        } else {
+            insert_discord_setting("member_role", &form.member_role, &mut *transaction).await?;
+        }
+        // Clear first so roles can move between generations without tripping the unique constraint
+        sqlx::query!("UPDATE generations SET discord_role = NULL")
+            .execute(&mut *transaction)
+            .await?;
+        for (generation_id, role) in &generation_roles {
+            sqlx::query!(
+                "UPDATE generations SET discord_role = $2 WHERE id = $1",
+                generation_id,
+                *role
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        let after = roles_snapshot(&mut transaction).await?;
+        AuditEvent {
+            account_id: Some(admin.account.id),
+            action: "config.discord_roles",
+            before: Some(before),
+            after: Some(after),
+            ..Default::default()
+        }
+        .record(&mut *transaction)
+        .await?;
+
+        transaction.commit().await
+    };
+
+    match result.await {
+        Ok(_) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Successfully updated Discord roles!"}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    }
+}
+
+fn no_role_alert() -> Markup {
+    html! {
+        ."alert"."alert-warning" {(icons::warning()) span {"No member or generation roles are configured."}}
+    }
+}
+
+pub async fn preview_changes(State(state): State<crate::AppState>) -> Markup {
+    let synced_roles = match roles::synced_roles(&state.db_pool).await {
+        Ok(synced_roles) if synced_roles.is_empty() => return no_role_alert(),
+        Ok(synced_roles) => synced_roles,
+        Err(err) => {
+            return html! {
+                ."alert"."alert-error" {(icons::error()) span {(err)}}
+            }
+        }
+    };
+    let changes = match roles::plan_changes(&synced_roles, &state).await {
+        Ok(changes) => changes,
+        Err(err) => {
+            return html! {
+                ."alert"."alert-error" {(icons::error()) span {(err)}}
+            }
+        }
+    };
+
+    html! {
+        @if changes.is_empty() {
+            ."alert"."alert-success" {(icons::success()) span {"All member roles are up to date."}}
+        } @else {
+            table ."table"."table-sm" {
+                thead { tr { th {"Member"} th {"Discord"} th {"Role"} th {"Change"} } }
+                tbody {
+                    @for change in &changes {
+                        tr {
+                            td {
+                                a href={"/admin/members?discord="(change.user_id)} target="_blank" ."btn"."btn-link" {(change.member_name)}
+                            }
+                            td {(change.username)}
+                            td {(change.role_name)}
+                            td {
+                                @if change.add {
+                                    ."badge"."badge-success" {"Add Role"}
+                                } @else {
+                                    ."badge"."badge-error" {"Remove Role"}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub async fn apply_changes(
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+) -> Markup {
+    if roles::synced_roles(&state.db_pool)
+        .await
+        .is_ok_and(|synced_roles| synced_roles.is_empty())
+    {
+        return no_role_alert();
+    }
+
+    match roles::reconcile(Some(admin.account.id), &state).await {
+        Ok(count) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Updated roles for "(count)" member(s)."}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span ."whitespace-pre-line" {(err)}}
+        },
+    }
+}
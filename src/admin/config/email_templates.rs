@@ -0,0 +1,435 @@
+use axum::{
+    extract::{NestedPath, Path, State},
+    Extension, Form,
+};
+use maud::{html, Markup, PreEscaped};
+use serde::Deserialize;
+
+use crate::{
+    db::audit_events::AuditEvent,
+    icons,
+    send_email::{
+        build_message, create_email_template, delete_email_template, get_email_sender,
+        get_email_template, list_email_templates, outbox, populate_email_template, sanitize_email,
+        update_email_template, validate_email_template, EmailTemplate, EmailValues,
+        BUILTIN_EMAIL_TEMPLATES,
+    },
+};
+
+fn email_template_form(nest: &str, email_template: Option<&EmailTemplate>) -> Markup {
+    let builtin = email_template.is_some_and(|email_template| {
+        BUILTIN_EMAIL_TEMPLATES.contains(&email_template.id.as_str())
+    });
+    let preview_url = format!("{}/email_preview", nest);
+    html! {
+        form hx-post={(nest)"/email_templates"@if let Some(email_template) = email_template {"/"(email_template.id)}}
+            hx-target="#email_templates_config" hx-swap="outerHTML" ."card"."card-compact"."bg-base-100"."my-2" { ."card-body" {
+            ."grid"."grid-cols-1"."md:grid-cols-3"."gap-2" {
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Key"} }
+                    input type="text" name="id" required pattern="[a-z0-9_]+" readonly[builtin]
+                        title="Lowercase letters, numbers and underscores"
+                        value=[email_template.map(|email_template| &email_template.id)] ."input"."input-bordered"."input-sm";
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Subject"} }
+                    input type="text" name="subject" required
+                        value=[email_template.map(|email_template| &email_template.subject)] ."input"."input-bordered"."input-sm";
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Description"} }
+                    input type="text" name="description"
+                        value=[email_template.map(|email_template| &email_template.description)] ."input"."input-bordered"."input-sm";
+                }
+            }
+            ."grid"."grid-cols-1"."lg:grid-cols-2"."gap-4"."my-2" {
+                ."flex"."flex-col"."gap-2" {
+                    label ."form-control" {
+                        ."label" { span ."label-text" {"HTML Body"} }
+                        textarea name="template" ."textarea"."textarea-primary"."font-mono"."w-full"."min-h-72"
+                            hx-post=(preview_url) hx-target="#email_template_preview" hx-trigger="load, input changed delay:500ms" {
+                            (email_template.map(|email_template| email_template.template.as_str()).unwrap_or_default())
+                        }
+                    }
+                    label ."form-control" {
+                        ."label" { span ."label-text" {"Plain Text Body (Optional)"} }
+                        textarea name="plaintext" ."textarea"."textarea-bordered"."font-mono"."w-full"."min-h-32"
+                            hx-post=(preview_url) hx-target="#email_template_preview" hx-trigger="input changed delay:500ms" {
+                            (email_template.and_then(|email_template| email_template.plaintext.as_deref()).unwrap_or_default())
+                        }
+                    }
+                }
+                ."flex"."flex-col"."gap-2" {
+                    input type="email" name="preview_email" placeholder="Preview with a member's data (email)"
+                        ."input"."input-bordered"."input-sm"."w-full"
+                        hx-post=(preview_url) hx-target="#email_template_preview" hx-trigger="input changed delay:500ms";
+                    #"email_template_preview" ."grow" {}
+                }
+            }
+            ."card-actions"."items-center"."justify-end" {
+                #"email_template_test_results" {}
+                button type="button" ."btn"."btn-sm"."btn-secondary"."btn-outline"
+                    hx-post={(nest)"/email_test"} hx-target="#email_template_test_results" {"SEND TEST TO ME"}
+                button ."btn"."btn-sm"."btn-primary" {(if email_template.is_some() {"SAVE"} else {"ADD TEMPLATE"})}
+            }
+        }}
+    }
+}
+
+async fn email_templates_list(
+    nest: &str,
+    state: &crate::AppState,
+    alert: Option<Markup>,
+    editing: Option<&EmailTemplate>,
+) -> Markup {
+    let email_templates = list_email_templates(&state.db_pool)
+        .await
+        .unwrap_or_default();
+
+    html! {
+        #"email_templates_config" {
+            @if let Some(alert) = alert { (alert) }
+            ."overflow-x-auto" { table ."table"."table-sm" {
+                thead { tr {
+                    th {"Key"}
+                    th {"Subject"}
+                    th {"Description"}
+                    th {}
+                }}
+                tbody {
+                    @for email_template in &email_templates {
+                        tr {
+                            td ."font-mono" {(email_template.id)}
+                            td {(email_template.subject)}
+                            td ."text-sm" {(email_template.description)}
+                            td ."text-right"."whitespace-nowrap" {
+                                button ."btn"."btn-xs"."btn-primary"
+                                    hx-get={(nest)"/email_templates/"(email_template.id)} hx-target="#email_template_editor" {"EDIT"}
+                                @if !BUILTIN_EMAIL_TEMPLATES.contains(&email_template.id.as_str()) {
+                                    " "
+                                    button ."btn"."btn-xs"."btn-error"."btn-outline"
+                                        hx-post={(nest)"/email_templates/"(email_template.id)"/delete"} hx-target="#email_templates_config" hx-swap="outerHTML"
+                                        hx-confirm={"Delete the "(email_template.id)" template?"} {"DELETE"}
+                                }
+                            }
+                        }
+                    }
+                }
+            }}
+            #"email_template_editor" {
+                @if let Some(email_template) = editing { (email_template_form(nest, Some(email_template))) }
+            }
+            ."divider" {"New Template"}
+            button ."btn"."btn-sm"."btn-outline"."block"."mx-auto"
+                hx-get={(nest)"/email_templates/new"} hx-target="#email_template_editor" {"NEW TEMPLATE"}
+        }
+    }
+}
+
+pub async fn email_templates_form(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    email_templates_list(nest.as_str(), &state, None, None).await
+}
+
+pub async fn new_email_template_form(nest: NestedPath) -> Markup {
+    email_template_form(nest.as_str(), None)
+}
+
+pub async fn edit_email_template_form(
+    nest: NestedPath,
+    Path(id): Path<String>,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    match get_email_template(&id, &state.db_pool).await {
+        Ok(email_template) => email_template_form(nest.as_str(), Some(&email_template)),
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EmailTemplateFormData {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    subject: String,
+    #[serde(default)]
+    description: String,
+    template: String,
+    #[serde(default)]
+    plaintext: String,
+    #[serde(default)]
+    preview_email: String,
+}
+
+impl EmailTemplateFormData {
+    fn email_template(&self) -> EmailTemplate {
+        EmailTemplate {
+            id: self.id.trim().to_owned(),
+            subject: self.subject.trim().to_owned(),
+            description: self.description.trim().to_owned(),
+            template: sanitize_email(&self.template),
+            plaintext: Some(self.plaintext.trim().to_owned())
+                .filter(|plaintext| !plaintext.is_empty()),
+        }
+    }
+}
+
+fn validate_bodies(email_template: &EmailTemplate) -> Result<(), String> {
+    validate_email_template(&email_template.template)
+        .map_err(|err| format!("HTML body: {}", err))?;
+    if let Some(plaintext) = &email_template.plaintext {
+        validate_email_template(plaintext).map_err(|err| format!("Plain text body: {}", err))?;
+    }
+    Ok(())
+}
+
+fn validate_form(email_template: &EmailTemplate) -> Result<(), String> {
+    if email_template.id.is_empty()
+        || !email_template
+            .id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(String::from(
+            "Key must be lowercase letters, numbers and underscores",
+        ));
+    }
+    if email_template.subject.is_empty() {
+        return Err(String::from("Subject is required"));
+    }
+    validate_bodies(email_template)
+}
+
+async fn preview_values(
+    preview_email: &str,
+    db_pool: &sqlx::PgPool,
+) -> Result<EmailValues, String> {
+    let mut values = EmailValues::sample();
+    let preview_email = preview_email.trim();
+    if preview_email.is_empty() {
+        return Ok(values);
+    }
+    let member = sqlx::query!(
+        "SELECT first_name, last_name, email FROM members WHERE id = member_id_by_email($1)",
+        preview_email
+    )
+    .fetch_optional(db_pool)
+    .await
+    .map_err(|err| err.to_string())?
+    .ok_or(format!("No member found with email {}", preview_email))?;
+    values.first_name = member.first_name;
+    values.last_name = member.last_name;
+    values.email = member.email;
+    Ok(values)
+}
+
+pub async fn email_preview(
+    State(state): State<crate::AppState>,
+    Form(form): Form<EmailTemplateFormData>,
+) -> Markup {
+    let email_template = form.email_template();
+    let preview = async {
+        validate_bodies(&email_template)?;
+        let values = preview_values(&form.preview_email, &state.db_pool).await?;
+        populate_email_template(&email_template.template, &values).map_err(|err| err.to_string())
+    };
+    match preview.await {
+        Ok(rendered) => html! {
+            ."card"."bg-base-100"."border"."border-base-300" {
+                ."card-body" {
+                    @if !email_template.subject.is_empty() {
+                        p ."font-bold" {(email_template.subject)}
+                        ."divider"."my-0" {}
+                    }
+                    (PreEscaped(rendered))
+                }
+            }
+        },
+        Err(err) => html! {
+            ."alert"."alert-warning" {(icons::warning()) span {(err)}}
+        },
+    }
+}
+
+pub async fn send_test_email(
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<EmailTemplateFormData>,
+) -> Markup {
+    let mut email_template = form.email_template();
+    email_template.subject = format!("[Test] {}", email_template.subject);
+    let result = async {
+        validate_bodies(&email_template)?;
+        let values = preview_values(&form.preview_email, &state.db_pool).await?;
+        let sender = get_email_sender(&state.db_pool).await?;
+        let message = build_message(&email_template, &sender, &admin.account.email, &values)?;
+        outbox::enqueue(
+            outbox::OutboxMessage {
+                email_key: &email_template.id,
+                subject: &email_template.subject,
+                to_address: &admin.account.email,
+                member_id: None,
+                campaign_id: None,
+                message,
+            },
+            &state.db_pool,
+        )
+        .await
+        .map_err(|err| err.to_string())
+    };
+    match result.await {
+        Ok(id) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Queued test email #"(id)" to "(admin.account.email)}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    }
+}
+
+fn map_template_error(err: sqlx::Error, id: &str) -> String {
+    match err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            format!("A template named '{}' already exists", id)
+        }
+        _ => err.to_string(),
+    }
+}
+
+async fn save_email_template(
+    previous_id: Option<&str>,
+    email_template: &EmailTemplate,
+    state: &crate::AppState,
+    admin: &crate::auth::Jwt,
+) -> Result<(), String> {
+    validate_form(email_template)?;
+    if previous_id.is_some_and(|previous_id| {
+        previous_id != email_template.id && BUILTIN_EMAIL_TEMPLATES.contains(&previous_id)
+    }) {
+        return Err(String::from("Built-in templates can't be renamed"));
+    }
+
+    let mut transaction = state.db_pool.begin().await.map_err(|err| err.to_string())?;
+    let before = match previous_id {
+        Some(previous_id) => Some(
+            get_email_template(previous_id, &mut *transaction)
+                .await
+                .map_err(|err| err.to_string())?,
+        ),
+        None => None,
+    };
+    match previous_id {
+        Some(previous_id) => {
+            update_email_template(previous_id, email_template, &mut *transaction).await
+        }
+        None => create_email_template(email_template, &mut *transaction).await,
+    }
+    .map_err(|err| map_template_error(err, &email_template.id))?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        action: if previous_id.is_some() {
+            "config.email_template"
+        } else {
+            "config.email_template_create"
+        },
+        reason: Some(&email_template.id),
+        before: before.map(|before| serde_json::json!(before)),
+        after: Some(serde_json::json!(email_template)),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    transaction.commit().await.map_err(|err| err.to_string())
+}
+
+async fn respond(
+    nest: &str,
+    state: &crate::AppState,
+    email_template: &EmailTemplate,
+    result: Result<(), String>,
+) -> Markup {
+    match result {
+        Ok(_) => {
+            let alert = html! {
+                ."alert"."alert-success" {(icons::success()) span {"Successfully saved email template!"}}
+            };
+            email_templates_list(nest, state, Some(alert), Some(email_template)).await
+        }
+        Err(err) => {
+            let alert = html! {
+                ."alert"."alert-error" {(icons::error()) span {"Template not saved: "(err)}}
+            };
+            email_templates_list(nest, state, Some(alert), None).await
+        }
+    }
+}
+
+pub async fn create_template(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<EmailTemplateFormData>,
+) -> Markup {
+    let email_template = form.email_template();
+    let result = save_email_template(None, &email_template, &state, &admin).await;
+    respond(nest.as_str(), &state, &email_template, result).await
+}
+
+pub async fn update_template(
+    nest: NestedPath,
+    Path(id): Path<String>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<EmailTemplateFormData>,
+) -> Markup {
+    let email_template = form.email_template();
+    let result = save_email_template(Some(&id), &email_template, &state, &admin).await;
+    respond(nest.as_str(), &state, &email_template, result).await
+}
+
+pub async fn delete_template(
+    nest: NestedPath,
+    Path(id): Path<String>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+) -> Markup {
+    let result = async {
+        if BUILTIN_EMAIL_TEMPLATES.contains(&id.as_str()) {
+            return Err(String::from("Built-in templates can't be deleted"));
+        }
+        let mut transaction = state.db_pool.begin().await.map_err(|err| err.to_string())?;
+        let before = get_email_template(&id, &mut *transaction)
+            .await
+            .map_err(|err| err.to_string())?;
+        delete_email_template(&id, &mut *transaction)
+            .await
+            .map_err(|err| err.to_string())?;
+        AuditEvent {
+            account_id: Some(admin.account.id),
+            action: "config.email_template_delete",
+            reason: Some(&id),
+            before: Some(serde_json::json!(before)),
+            ..Default::default()
+        }
+        .record(&mut *transaction)
+        .await
+        .map_err(|err| err.to_string())?;
+        transaction.commit().await.map_err(|err| err.to_string())
+    };
+    let alert = match result.await {
+        Ok(_) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Deleted the "(id)" template"}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    };
+    email_templates_list(nest.as_str(), &state, Some(alert), None).await
+}
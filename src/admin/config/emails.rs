@@ -1,60 +1,21 @@
 use axum::{
     extract::{NestedPath, Path, Query, State},
     response::{IntoResponse, Response},
-    Form,
+    Extension, Form,
 };
-use lettre::{message::Mailbox, AsyncTransport};
+use lettre::message::Mailbox;
 use maud::{html, Markup};
 use reqwest::StatusCode;
 use serde::Deserialize;
 use tokio::try_join;
 
 use crate::{
+    db::audit_events::AuditEvent,
     err_responses::{ErrorResponse, MapErrorResponse},
     icons,
-    send_email::{
-        build_mailer, build_message, get_email_address, get_email_template, insert_email_address,
-        insert_email_template, sanitize_email, EmailValues,
-    },
+    send_email::{get_email_address, insert_email_address, queue_email, EmailValues},
 };
 
-pub async fn email_contents_form(
-    nest: NestedPath,
-    Path(email_key): Path<String>,
-    State(state): State<crate::AppState>,
-) -> Markup {
-    html! {
-        #{(email_key)"_email_results"} {}
-        form hx-post={(nest.as_str())"/email_contents/"(email_key)} hx-target={"#"(email_key)"_email_results"} {
-            textarea name="email_body" ."textarea"."textarea-primary"."font-mono"."my-4"."w-full" required {
-                (get_email_template(&email_key, &state.db_pool).await.unwrap_or_default())
-            }
-            button ."btn"."btn-primary"."w-1/2"."block"."mx-auto"."!mb-0" {"UPDATE"}
-        }
-    }
-}
-
-#[derive(Deserialize)]
-pub struct EmailFormData {
-    email_body: String,
-}
-
-pub async fn set_email_contents(
-    Path(email_key): Path<String>,
-    State(state): State<crate::AppState>,
-    Form(form): Form<EmailFormData>,
-) -> Markup {
-    let content = sanitize_email(&form.email_body);
-    match insert_email_template(&email_key, &content, &state.db_pool).await {
-        Ok(_) => html! {
-            ."alert"."alert-success" {(icons::success()) span {"Successfully updated email contents!"}}
-        },
-        Err(err) => html! {
-            ."alert"."alert-error" {(icons::error()) span {(err)}}
-        },
-    }
-}
-
 pub async fn email_addresses_form(
     nest: NestedPath,
     State(state): State<crate::AppState>,
@@ -94,6 +55,7 @@ pub struct EmailAddressesFormData {
 
 pub async fn set_email_addresses(
     State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
     Form(form): Form<EmailAddressesFormData>,
 ) -> Markup {
     if let Err(err) = form.from_address.parse::<Mailbox>() {
@@ -111,6 +73,12 @@ pub async fn set_email_addresses(
             ."alert"."alert-error" {(icons::error()) span {"Invalid 'Board Notification' Address: "(err)}}
         };
     }
+    let previous = try_join!(
+        get_email_address("from", &state.db_pool),
+        get_email_address("replyto", &state.db_pool),
+        get_email_address("board_notif", &state.db_pool)
+    )
+    .ok();
     if let Err(err) = try_join!(
         insert_email_address("from", &form.from_address, &state.db_pool),
         insert_email_address("replyto", &form.replyto_address, &state.db_pool),
@@ -120,6 +88,26 @@ pub async fn set_email_addresses(
             ."alert"."alert-error" {(icons::error()) span {(err)}}
         };
     }
+    if let Err(err) = (AuditEvent {
+        account_id: Some(admin.account.id),
+        action: "config.email_addresses",
+        before: previous.map(|(from, replyto, board_notif)| {
+            serde_json::json!({ "from": from, "replyto": replyto, "board_notif": board_notif })
+        }),
+        after: Some(serde_json::json!({
+            "from": form.from_address,
+            "replyto": form.replyto_address,
+            "board_notif": form.board_notif_address,
+        })),
+        ..Default::default()
+    })
+    .record(&state.db_pool)
+    .await
+    {
+        return html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        };
+    }
     html! {."alert"."alert-success" {(icons::success()) span {"Successfully updated email addresses!"}}}
 }
 
@@ -128,31 +116,13 @@ pub async fn send_email(
     State(state): State<crate::AppState>,
     Query(params): Query<EmailValues>,
 ) -> Result<Response, Response> {
-    let mailer = build_mailer(&state)
+    let mut conn = state
+        .db_pool
+        .acquire()
         .await
         .map_err_response(ErrorResponse::InternalServerError)?;
-    let message = build_message(
-        &email_key,
-        "Psychedelic Club Discord",
-        &params.email,
-        &params,
-        &state,
-    )
-    .await
-    .map_err_response(ErrorResponse::InternalServerError)?;
-    mailer
-        .send(message)
+    queue_email(&email_key, &params.email, &params, None, &mut conn)
         .await
-        .map(|resp| {
-            (
-                StatusCode::OK,
-                format!(
-                    "{} {}",
-                    resp.code().to_string(),
-                    resp.first_line().unwrap_or_default()
-                ),
-            )
-                .into_response()
-        })
+        .map(|id| (StatusCode::OK, format!("Queued as email #{}", id)).into_response())
         .map_err_response(ErrorResponse::InternalServerError)
 }
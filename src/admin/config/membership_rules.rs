@@ -0,0 +1,169 @@
+use std::str::FromStr;
+
+use axum::{
+    extract::{NestedPath, State},
+    Extension,
+};
+use axum_extra::extract::Form;
+use maud::{html, Markup};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::{db::audit_events::AuditEvent, icons};
+
+struct MembershipPrice {
+    amount: Decimal,
+    duration_months: i32,
+}
+
+async fn rules_snapshot(db: impl sqlx::PgExecutor<'_>) -> Result<serde_json::Value, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT jsonb_build_object(
+                'grace_period_days', (SELECT grace_period_days FROM membership_rules),
+                'prices', COALESCE((SELECT jsonb_object_agg(amount, duration_months) FROM membership_prices), '{}')
+            ) AS "rules!""#
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn membership_rules_form(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    let grace_period_days = sqlx::query_scalar!("SELECT grace_period_days FROM membership_rules")
+        .fetch_optional(&state.db_pool)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(7);
+    let prices = sqlx::query_as!(
+        MembershipPrice,
+        "SELECT amount, duration_months FROM membership_prices ORDER BY amount"
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
+
+    html! {
+        #"membership_rules_results" {}
+        form hx-post={(nest.as_str())"/membership_rules"} hx-target="#membership_rules_results" {
+            label ."form-control"."w-full"."max-w-lg"."mx-auto" {
+                ."label" { span ."label-text" {"Grace Period (Days)"} }
+                input type="number" name="grace_period_days" min="0" step="1" required value=(grace_period_days) ."input"."input-bordered"."w-full";
+                ."label" { span ."label-text-alt" {"How long after a payment's duration runs out before the member is considered inactive or their streak is broken."} }
+            }
+            ."divider" {"Price Table"}
+            p ."text-sm"."max-w-lg"."mx-auto"."mb-2" {
+                "Payments received through Webconnex and Donorbox take the duration of the active plan with exactly that price. Otherwise they get the duration of the most expensive price they cover, or 1 month. Clear an amount to remove its row."
+            }
+            table ."table"."max-w-lg"."mx-auto" {
+                thead { tr { th {"Amount Paid ($)"} th {"Duration (Months)"} } }
+                @for price in &prices {
+                    tr {
+                        td { input type="number" name="amount" min="0" step="0.01" value=(price.amount) ."input"."input-bordered"."input-sm"."w-full"; }
+                        td { input type="number" name="duration_months" min="1" step="1" value=(price.duration_months) ."input"."input-bordered"."input-sm"."w-full"; }
+                    }
+                }
+                @for _ in 0..3 {
+                    tr {
+                        td { input type="number" name="amount" min="0" step="0.01" ."input"."input-bordered"."input-sm"."w-full"; }
+                        td { input type="number" name="duration_months" min="1" step="1" ."input"."input-bordered"."input-sm"."w-full"; }
+                    }
+                }
+            }
+            button ."btn"."btn-primary"."w-1/2"."block"."mx-auto"."!mb-0"."mt-2" {"UPDATE"}
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MembershipRulesFormData {
+    grace_period_days: i32,
+    #[serde(default)]
+    amount: Vec<String>,
+    #[serde(default)]
+    duration_months: Vec<String>,
+}
+
+pub async fn set_membership_rules(
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<MembershipRulesFormData>,
+) -> Markup {
+    if form.grace_period_days < 0 {
+        return html! {
+            ."alert"."alert-error" {(icons::error()) span {"Grace period cannot be negative"}}
+        };
+    }
+
+    let mut amounts = Vec::new();
+    let mut durations = Vec::new();
+    for (amount, duration) in form.amount.iter().zip(form.duration_months.iter()) {
+        if amount.trim().is_empty() {
+            continue;
+        }
+        match (
+            Decimal::from_str(amount.trim()),
+            duration.trim().parse::<i32>(),
+        ) {
+            (Ok(amount), Ok(duration)) if duration > 0 && !amount.is_sign_negative() => {
+                amounts.push(amount);
+                durations.push(duration);
+            }
+            _ => {
+                return html! {
+                    ."alert"."alert-error" {(icons::error()) span {"Invalid price row: $"(amount)" for '"(duration)"' months"}}
+                };
+            }
+        }
+    }
+
+    let result = async {
+        let mut transaction = state.db_pool.begin().await?;
+        let before = rules_snapshot(&mut *transaction).await?;
+
+        sqlx::query!(
+            "UPDATE membership_rules SET grace_period_days = $1",
+            form.grace_period_days
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!("DELETE FROM membership_prices")
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query!(
+            r#"INSERT INTO membership_prices (amount, duration_months)
+                SELECT * FROM UNNEST($1::NUMERIC[], $2::INT[])"#,
+            &amounts,
+            &durations
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let after = rules_snapshot(&mut *transaction).await?;
+        AuditEvent {
+            account_id: Some(admin.account.id),
+            action: "config.membership_rules",
+            before: Some(before),
+            after: Some(after),
+            ..Default::default()
+        }
+        .record(&mut *transaction)
+        .await?;
+
+        transaction.commit().await
+    };
+
+    match result.await {
+        Ok(_) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Successfully updated membership rules!"}}
+        },
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => html! {
+            ."alert"."alert-error" {(icons::error()) span {"Each amount can only appear once in the price table"}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    }
+}
@@ -1,9 +1,21 @@
-use axum::{extract::NestedPath, routing::get, Router};
+use axum::{
+    extract::NestedPath,
+    routing::{get, post},
+    Router,
+};
 use maud::{html, Markup};
 
 use crate::icons;
 
+mod discord_accounts;
+mod discord_bans;
+mod discord_client;
+mod discord_notifications;
+mod discord_roles;
+mod email_templates;
 mod emails;
+mod membership_rules;
+pub mod plans;
 
 async fn home(nest: NestedPath) -> Markup {
     html! { #"mdma-config" ."w-full"."max-w-4xl"."mx-auto" {
@@ -12,18 +24,48 @@ async fn home(nest: NestedPath) -> Markup {
             span {"Warning: Here be dragons! 🐉 Seriously, make sure you know what you're doing on this page..."}
         }
         ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
-            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/email_addresses"} hx-target="next .collapse-content";
-            ."collapse-title"."text-xl"."font-medium" {"Email Addresses"}
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/membership_rules"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Membership Rules"}
+            ."collapse-content" {}
+        }
+        ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/plans"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Membership Plans"}
+            ."collapse-content" {}
+        }
+        ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/discord_client"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Discord Connection"}
+            ."collapse-content" {}
+        }
+        ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/discord_roles"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Discord Role Sync"}
             ."collapse-content" {}
         }
         ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
-            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/email_contents/discord"} hx-target="next .collapse-content";
-            ."collapse-title"."text-xl"."font-medium" {"Discord Email Contents"}
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/discord_accounts"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Discord Admin Accounts"}
+            ."collapse-content" {}
+        }
+        ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/discord_notifications"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Discord Notifications"}
+            ."collapse-content" {}
+        }
+        ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/discord_bans"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Discord Ban Report"}
+            ."collapse-content" {}
+        }
+        ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/email_addresses"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Email Addresses"}
             ."collapse-content" {}
         }
         ."collapse"."collapse-arrow"."bg-base-200"."my-4"."border"."border-secondary" {
-            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/email_contents/board_notif"} hx-target="next .collapse-content";
-            ."collapse-title"."text-xl"."font-medium" {"Exec Board Notification Email Contents"}
+            input type="radio" name="config-accordion" hx-get={(nest.as_str())"/email_templates"} hx-target="next .collapse-content";
+            ."collapse-title"."text-xl"."font-medium" {"Email Templates"}
             ."collapse-content" {}
         }
     }}
@@ -33,10 +75,58 @@ pub fn router(state: crate::AppState) -> Router {
     Router::new()
         .route("/", get(home))
         .route(
-            "/email_contents/{email_key}",
-            get(emails::email_contents_form).post(emails::set_email_contents),
+            "/email_templates",
+            get(email_templates::email_templates_form).post(email_templates::create_template),
+        )
+        .route(
+            "/email_templates/new",
+            get(email_templates::new_email_template_form),
         )
+        .route(
+            "/email_templates/{id}",
+            get(email_templates::edit_email_template_form).post(email_templates::update_template),
+        )
+        .route(
+            "/email_templates/{id}/delete",
+            post(email_templates::delete_template),
+        )
+        .route("/email_preview", post(email_templates::email_preview))
+        .route("/email_test", post(email_templates::send_test_email))
         .route("/send_email/{email_key}", get(emails::send_email))
+        .route(
+            "/membership_rules",
+            get(membership_rules::membership_rules_form)
+                .post(membership_rules::set_membership_rules),
+        )
+        .route("/plans", get(plans::plans_form).post(plans::create_plan))
+        .route("/plans/{plan_id}", post(plans::update_plan))
+        .route(
+            "/discord_accounts",
+            get(discord_accounts::discord_accounts_form),
+        )
+        .route(
+            "/discord_accounts/{account_id}",
+            post(discord_accounts::set_discord_account),
+        )
+        .route("/discord_bans", get(discord_bans::discord_bans_report))
+        .route(
+            "/discord_client",
+            get(discord_client::discord_client_status),
+        )
+        .route(
+            "/discord_notifications",
+            get(discord_notifications::discord_notifications_form)
+                .post(discord_notifications::set_discord_notifications),
+        )
+        .route(
+            "/discord_roles",
+            get(discord_roles::discord_roles_form).post(discord_roles::set_discord_role),
+        )
+        .route(
+            "/discord_roles/preview",
+            get(discord_roles::preview_changes),
+        )
+        .route("/discord_roles/apply", post(discord_roles::apply_changes))
         .route(
             "/email_addresses",
             get(emails::email_addresses_form).post(emails::set_email_addresses),
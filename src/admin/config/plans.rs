@@ -0,0 +1,203 @@
+use axum::{
+    extract::{NestedPath, Path, State},
+    Extension,
+};
+use axum_extra::extract::Form;
+use maud::{html, Markup};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::{
+    db::{audit_events::AuditEvent, plans::PlanRow},
+    icons,
+};
+
+pub fn plan_select(plans: &[PlanRow], selected: Option<i32>) -> Markup {
+    html! {
+        select name="plan_id" ."select"."select-bordered"
+            onchange="const opt = this.selectedOptions[0]; if (opt.dataset.price) { this.form.amount_paid.value = opt.dataset.price; this.form.duration_months.value = opt.dataset.duration; }" {
+            option value="" {"(No Plan)"}
+            @for plan in plans {
+                option value=(plan.id) selected[selected == Some(plan.id)] data-price=(plan.price.round_dp(2)) data-duration=(plan.duration_months) {
+                    (plan.name)" ($"(plan.price.round_dp(2))" / "(plan.duration_months)" mo.)"
+                }
+            }
+        }
+    }
+}
+
+fn plan_form(nest: &str, plan: Option<&PlanRow>) -> Markup {
+    html! {
+        form hx-post={(nest)"/plans"@if let Some(plan) = plan {"/"(plan.id)}} hx-target="#plans_config" hx-swap="outerHTML" ."card"."card-compact"."bg-base-100"."my-2" { ."card-body" {
+            ."grid"."grid-cols-1"."md:grid-cols-3"."gap-2" {
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Name"} }
+                    input type="text" name="name" required value=[plan.map(|plan| &plan.name)] ."input"."input-bordered"."input-sm";
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Price ($)"} }
+                    input type="number" name="price" required min="0" step="0.01" value=[plan.map(|plan| plan.price.round_dp(2))] ."input"."input-bordered"."input-sm";
+                }
+                label ."form-control" {
+                    ."label" { span ."label-text" {"Duration (Months)"} }
+                    input type="number" name="duration_months" required min="1" step="1" value=[plan.map(|plan| plan.duration_months)] ."input"."input-bordered"."input-sm";
+                }
+            }
+            label ."form-control" {
+                ."label" { span ."label-text" {"Benefits"} }
+                textarea name="benefits" ."textarea"."textarea-bordered" {(plan.map(|plan| plan.benefits.as_str()).unwrap_or_default())}
+            }
+            ."card-actions"."items-center"."justify-end" {
+                @if let Some(plan) = plan {
+                    label ."label"."cursor-pointer"."gap-2" {
+                        span ."label-text" {"Archived"}
+                        input type="checkbox" name="archived" value="true" checked[plan.archived] ."checkbox"."checkbox-sm";
+                    }
+                }
+                button ."btn"."btn-sm"."btn-primary" {(if plan.is_some() {"SAVE"} else {"ADD PLAN"})}
+            }
+        }}
+    }
+}
+
+async fn plans_list(nest: &str, state: &crate::AppState, alert: Option<Markup>) -> Markup {
+    let plans = sqlx::query_as!(
+        PlanRow,
+        "SELECT * FROM plans ORDER BY archived, price, duration_months"
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
+
+    html! {
+        #"plans_config" {
+            @if let Some(alert) = alert { (alert) }
+            p ."text-sm" {
+                "Webconnex and Donorbox payments are linked to the active plan with exactly the amount paid. Archived plans can no longer be chosen for new payments."
+            }
+            @for plan in &plans {
+                (plan_form(nest, Some(plan)))
+            }
+            ."divider" {"New Plan"}
+            (plan_form(nest, None))
+        }
+    }
+}
+
+pub async fn plans_form(nest: NestedPath, State(state): State<crate::AppState>) -> Markup {
+    plans_list(nest.as_str(), &state, None).await
+}
+
+#[derive(Deserialize)]
+pub struct PlanFormData {
+    name: String,
+    price: Decimal,
+    duration_months: i32,
+    #[serde(default)]
+    benefits: String,
+    #[serde(default)]
+    archived: bool,
+}
+
+async fn save_plan(
+    plan_id: Option<i32>,
+    state: &crate::AppState,
+    admin: &crate::auth::Jwt,
+    form: &PlanFormData,
+) -> Result<(), String> {
+    if form.name.trim().is_empty() {
+        return Err(String::from("Plan name is required"));
+    }
+    if form.duration_months < 1 || form.price.is_sign_negative() {
+        return Err(String::from("Invalid price or duration"));
+    }
+
+    let mut transaction = state.db_pool.begin().await.map_err(|err| err.to_string())?;
+    let before = match plan_id {
+        Some(plan_id) => Some(
+            sqlx::query_scalar!(
+                r#"SELECT to_jsonb(plans) AS "plan!" FROM plans WHERE id = $1 FOR UPDATE"#,
+                plan_id
+            )
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|err| err.to_string())?,
+        ),
+        None => None,
+    };
+    let after = sqlx::query_scalar!(
+        r#"INSERT INTO plans (id, name, price, duration_months, benefits, archived)
+            VALUES (COALESCE($1::INT, NEXTVAL('plans_id_seq')::INT), $2, $3, $4, $5, $6)
+            ON CONFLICT (id) DO UPDATE SET
+                name = excluded.name,
+                price = excluded.price,
+                duration_months = excluded.duration_months,
+                benefits = excluded.benefits,
+                archived = excluded.archived
+            RETURNING to_jsonb(plans) AS "plan!""#,
+        plan_id,
+        form.name.trim(),
+        form.price,
+        form.duration_months,
+        form.benefits.trim(),
+        form.archived
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            format!("A plan named '{}' already exists", form.name.trim())
+        }
+        _ => err.to_string(),
+    })?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        action: if plan_id.is_some() {
+            "config.plan_edit"
+        } else {
+            "config.plan_create"
+        },
+        before,
+        after: Some(after),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    transaction.commit().await.map_err(|err| err.to_string())
+}
+
+async fn respond(nest: &str, state: &crate::AppState, result: Result<(), String>) -> Markup {
+    let alert = match result {
+        Ok(_) => html! {
+            ."alert"."alert-success" {(icons::success()) span {"Successfully saved plan!"}}
+        },
+        Err(err) => html! {
+            ."alert"."alert-error" {(icons::error()) span {(err)}}
+        },
+    };
+    plans_list(nest, state, Some(alert)).await
+}
+
+pub async fn create_plan(
+    nest: NestedPath,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<PlanFormData>,
+) -> Markup {
+    let result = save_plan(None, &state, &admin, &form).await;
+    respond(nest.as_str(), &state, result).await
+}
+
+pub async fn update_plan(
+    nest: NestedPath,
+    Path(plan_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<PlanFormData>,
+) -> Markup {
+    let result = save_plan(Some(plan_id), &state, &admin, &form).await;
+    respond(nest.as_str(), &state, result).await
+}
@@ -0,0 +1,80 @@
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use futures::{stream, Stream, StreamExt};
+
+fn csv_line<I, T>(record: I) -> Result<Vec<u8>, BoxError>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(record)?;
+    Ok(writer.into_inner().map_err(|err| err.into_error())?)
+}
+
+fn escape_formula(cell: String) -> String {
+    let formula = cell.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    if formula && cell.parse::<f64>().is_err() {
+        format!("'{}", cell)
+    } else {
+        cell
+    }
+}
+
+pub fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|val| val.to_string()).unwrap_or_default()
+}
+
+pub fn csv_response<R, S>(
+    filename: &str,
+    header: &'static [&'static str],
+    rows: S,
+    to_record: fn(R) -> Vec<String>,
+) -> Response
+where
+    R: 'static,
+    S: Stream<Item = Result<R, sqlx::Error>> + Send + 'static,
+{
+    let body = stream::once(async move { csv_line(header) }).chain(rows.map(
+        move |row| -> Result<Vec<u8>, BoxError> {
+            csv_line(to_record(row?).into_iter().map(escape_formula))
+        },
+    ));
+
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                String::from("text/csv; charset=utf-8"),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(r#"attachment; filename="{}""#, filename),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_formula;
+
+    #[test]
+    fn escapes_formula_cells() {
+        assert_eq!(
+            escape_formula(String::from("=HYPERLINK(\"x\")")),
+            "'=HYPERLINK(\"x\")"
+        );
+        assert_eq!(escape_formula(String::from("@SUM(A1)")), "'@SUM(A1)");
+        assert_eq!(escape_formula(String::from("+1-2+cmd")), "'+1-2+cmd");
+        assert_eq!(escape_formula(String::from("Jane")), "Jane");
+        assert_eq!(escape_formula(String::from("-12.50")), "-12.50");
+        assert_eq!(escape_formula(String::new()), "");
+    }
+}
@@ -1,4 +1,4 @@
-use axum::extract::State;
+use axum::extract::{NestedPath, State};
 use maud::{html, Markup};
 use time::Date;
 
@@ -10,7 +10,6 @@ pub struct GenerationStats {
     start_date: Date,
     total_members: i64,
     active_members: i64,
-    active_emails: Vec<String>,
 }
 
 impl GenerationStats {
@@ -19,21 +18,19 @@ impl GenerationStats {
     }
 }
 
-pub async fn generations_list(State(state): State<crate::AppState>) -> Markup {
+pub async fn generations_list(nest: NestedPath, State(state): State<crate::AppState>) -> Markup {
     let generations = sqlx::query_as!(
         GenerationStats,
         r#"SELECT 
             id AS "id!",
             total_members AS "total_members!",
             active_members AS "active_members!",
-            COALESCE(active_emails, '{}') AS "active_emails!",
             title,
             start_date
         FROM 
             (SELECT generation_id AS id,
                     COUNT(*) AS total_members,
-                    COUNT(*) FILTER (WHERE is_active(id)) AS active_members,
-                    ARRAY_AGG(CONCAT(first_name, ' ', last_name, ' <', email, '>')) FILTER (WHERE is_active(id)) AS active_emails
+                    COUNT(*) FILTER (WHERE is_active(id)) AS active_members
             FROM member_generations
                 INNER JOIN members ON members.id = member_id
             GROUP BY generation_id) temp1
@@ -46,11 +43,6 @@ pub async fn generations_list(State(state): State<crate::AppState>) -> Markup {
 
     let total_members: i64 = generations.iter().map(|gen| gen.total_members).sum();
     let active_members: i64 = generations.iter().map(|gen| gen.active_members).sum();
-    let all_emails = generations
-        .iter()
-        .map(|gen| gen.active_emails.join(";"))
-        .collect::<Vec<String>>()
-        .join(";");
 
     html! {
         table #"generations-list"."table"."mx-auto" {
@@ -74,16 +66,9 @@ pub async fn generations_list(State(state): State<crate::AppState>) -> Markup {
                         td {(format!("{:.1}", gen.percent_active() * 100.0)) "%"}
                         td ."*:mx-1" {
                             ."tooltip" data-tip="Email Active Members" {
-                                a ."btn"."btn-circle"."btn-outline"."btn-secondary"
-                                    href={"mailto:?bcc=" (gen.active_emails.join(","))}
+                                button ."btn"."btn-circle"."btn-outline"."btn-secondary"
+                                    hx-get={(nest.as_str())"/campaigns/new?member_status=true&generation_id="(gen.id)} hx-target="main" hx-push-url="true"
                                     { (icons::envelope()) }
-
-                            }
-                            ."tooltip" data-tip="Copy Active Member Emails" {
-                                a ."btn"."btn-circle"."btn-outline"."btn-secondary"
-                                    href="#"
-                                    onclick={"navigator.clipboard.writeText('" (gen.active_emails.join(",")) "')"}
-                                    { (icons::copy()) }
                             }
                         }
                     }
@@ -97,16 +82,10 @@ pub async fn generations_list(State(state): State<crate::AppState>) -> Markup {
                     td {(format!("{:.1}", active_members as f64 / total_members as f64 * 100.0)) "%"}
                     td ."rounded-br-lg"."*:mx-1" {
                         ."tooltip" data-tip="Email Active Members" {
-                            a ."btn"."btn-circle"."btn-outline"."btn-primary"
-                                href={"mailto:?bcc=" (all_emails)}
+                            button ."btn"."btn-circle"."btn-outline"."btn-primary"
+                                hx-get={(nest.as_str())"/campaigns/new?member_status=true"} hx-target="main" hx-push-url="true"
                                 { (icons::envelope()) }
                         }
-                        ."tooltip" data-tip="Copy Active Member Emails" {
-                            a ."btn"."btn-circle"."btn-outline"."btn-primary"
-                                href="#"
-                                onclick={"navigator.clipboard.writeText('" (all_emails) "')"}
-                                { (icons::copy()) }
-                        }
                     }
                 }
             }
@@ -4,9 +4,16 @@ use axum::{
     Extension, Form,
 };
 use maud::{html, Markup, PreEscaped};
+use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use crate::{components, db::members::MemberRow, err_responses::MapErrorResponse, icons};
+use crate::{
+    components,
+    db::members::MemberRow,
+    discord::bans::{self, GuildBanAction},
+    err_responses::MapErrorResponse,
+    icons,
+};
 
 #[derive(Deserialize)]
 pub struct CancelFormData {
@@ -14,6 +21,15 @@ pub struct CancelFormData {
     reason: String,
 }
 
+async fn linked_discord(
+    member_id: i32,
+    state: &crate::AppState,
+) -> Result<Option<Decimal>, sqlx::Error> {
+    sqlx::query_scalar!("SELECT discord FROM members WHERE id = $1", member_id)
+        .fetch_one(&state.db_pool)
+        .await
+}
+
 pub async fn cancel_form(
     nest: NestedPath,
     Path(member_id): Path<i32>,
@@ -50,24 +66,15 @@ pub async fn cancel_member(
     Extension(admin): Extension<crate::auth::Jwt>,
     Form(CancelFormData { reason }): Form<CancelFormData>,
 ) -> Result<Markup, Response> {
-    sqlx::query!(
-        r#" UPDATE members
-            SET
-                cancelled = TRUE,
-                notes = TRIM(E'\n' FROM notes || E'\n\n=== ' || CURRENT_DATE || E' ===\n' || $2)
-            WHERE id = $1"#,
+    crate::db::members::update_status(
         member_id,
-        format!(
-            "Cancelled by admin {} : {}",
-            admin.account.email,
-            if reason.trim().is_empty() {
-                "(No reason given)"
-            } else {
-                reason.trim()
-            }
-        )
+        admin.account.id,
+        "member.cancel",
+        &reason,
+        Some(true),
+        None,
+        &state.db_pool,
     )
-    .execute(&state.db_pool)
     .await
     .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
 
@@ -104,6 +111,16 @@ pub async fn ban_form(
                     span ."label-text" {"Ban Reason"}
                 }
                 input type="text" required name="reason" ."input"."input-bordered"."w-full";
+                @if member.discord.is_some() {
+                    ."label"."mt-2" {
+                        span ."label-text" {"Discord Account"}
+                    }
+                    select name="guild_action" ."select"."select-bordered"."w-full" {
+                        option value="ban" selected {"Ban from the Discord server"}
+                        option value="kick" {"Kick from the Discord server"}
+                        option value="nothing" {"Leave in the Discord server"}
+                    }
+                }
                 ."alert"."alert-warning"."mt-4"."w-full" role="warning" {
                     (icons::warning())
                     span {"Banning through MDMA does not cancel any subscriptions on third-party payment services."}
@@ -114,34 +131,54 @@ pub async fn ban_form(
     })
 }
 
+#[derive(Deserialize)]
+pub struct BanFormData {
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    guild_action: GuildBanAction,
+}
+
 pub async fn ban_member(
     Path(member_id): Path<i32>,
     State(state): State<crate::AppState>,
     Extension(admin): Extension<crate::auth::Jwt>,
-    Form(CancelFormData { reason }): Form<CancelFormData>,
+    Form(BanFormData {
+        reason,
+        guild_action,
+    }): Form<BanFormData>,
 ) -> Result<Markup, Response> {
     if reason.trim().is_empty() {
         return Err("Must provide a reason")
             .map_err_response(crate::err_responses::ErrorResponse::Alert);
     }
 
-    sqlx::query!(
-        r#" UPDATE members
-            SET
-                banned = TRUE,
-                notes = TRIM(E'\n' FROM notes || E'\n\n=== ' || CURRENT_DATE || E' ===\n' || $2)
-            WHERE id = $1"#,
+    crate::db::members::update_status(
         member_id,
-        format!(
-            "Banned by admin {} : {}",
-            admin.account.email,
-            reason.trim(),
-        )
+        admin.account.id,
+        "member.ban",
+        &reason,
+        None,
+        Some(true),
+        &state.db_pool,
     )
-    .execute(&state.db_pool)
     .await
     .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
 
+    let discord_result = match linked_discord(member_id, &state).await {
+        Ok(discord) => bans::ban_linked_user(
+            member_id,
+            discord,
+            guild_action,
+            &reason,
+            admin.account.id,
+            &state,
+        )
+        .await
+        .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
     Ok(html! {
         div hx-swap-oob={"innerHTML:#user_details_"(member_id)} {
             progress ."progress"."htmx-indicator" {
@@ -152,6 +189,9 @@ pub async fn ban_member(
             }
         }
         (components::ToastAlert::Success("Banned Member Successfully"))
+        @if let Err(err) = discord_result {
+            (components::ToastAlert::Error(&format!("Discord action failed: {}", err)))
+        }
     })
 }
 
@@ -175,40 +215,64 @@ pub async fn unban_form(
                     span ."label-text" {"Unban Reason"}
                 }
                 input type="text" required name="reason" ."input"."input-bordered"."w-full";
+                @if member.discord.is_some() {
+                    label ."label"."cursor-pointer"."mt-2" {
+                        span ."label-text" {"Lift the Discord server ban"}
+                        input type="checkbox" name="lift_guild_ban" value="true" checked ."checkbox";
+                    }
+                }
                 ."form-control"."mt-4" { button ."btn"."btn-outline"."btn-primary"."w-1/2"."mx-auto" {(icons::warning())" SUBMIT"} }
             }
         }
     })
 }
 
+#[derive(Deserialize)]
+pub struct UnbanFormData {
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    lift_guild_ban: bool,
+}
+
 pub async fn unban_member(
     Path(member_id): Path<i32>,
     State(state): State<crate::AppState>,
     Extension(admin): Extension<crate::auth::Jwt>,
-    Form(CancelFormData { reason }): Form<CancelFormData>,
+    Form(UnbanFormData {
+        reason,
+        lift_guild_ban,
+    }): Form<UnbanFormData>,
 ) -> Result<Markup, Response> {
     if reason.trim().is_empty() {
         return Err("Must provide a reason")
             .map_err_response(crate::err_responses::ErrorResponse::Alert);
     }
 
-    sqlx::query!(
-        r#" UPDATE members
-            SET
-                banned = FALSE,
-                notes = TRIM(E'\n' FROM notes || E'\n\n=== ' || CURRENT_DATE || E' ===\n' || $2)
-            WHERE id = $1"#,
+    crate::db::members::update_status(
         member_id,
-        format!(
-            "Unbanned by admin {} : {}",
-            admin.account.email,
-            reason.trim(),
-        )
+        admin.account.id,
+        "member.unban",
+        &reason,
+        None,
+        Some(false),
+        &state.db_pool,
     )
-    .execute(&state.db_pool)
     .await
     .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
 
+    let discord_result = match lift_guild_ban {
+        false => Ok(()),
+        true => match linked_discord(member_id, &state).await {
+            Ok(discord) => {
+                bans::unban_linked_user(member_id, discord, &reason, admin.account.id, &state)
+                    .await
+                    .map_err(|err| err.to_string())
+            }
+            Err(err) => Err(err.to_string()),
+        },
+    };
+
     Ok(html! {
         div hx-swap-oob={"innerHTML:#user_details_"(member_id)} {
             progress ."progress"."htmx-indicator" {
@@ -219,5 +283,8 @@ pub async fn unban_member(
             }
         }
         (components::ToastAlert::Success("Unbanned Member Successfully"))
+        @if let Err(err) = discord_result {
+            (components::ToastAlert::Error(&format!("Discord action failed: {}", err)))
+        }
     })
 }
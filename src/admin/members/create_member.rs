@@ -3,12 +3,12 @@ use std::str::FromStr;
 use axum::{
     extract::{NestedPath, State},
     response::Response,
-    Form,
+    Extension, Form,
 };
 use maud::{html, Markup};
 use serde::Deserialize;
 
-use crate::{components, err_responses::MapErrorResponse};
+use crate::{components, db::audit_events::AuditEvent, err_responses::MapErrorResponse};
 
 pub async fn member_form(nest: NestedPath) -> Markup {
     html! {
@@ -49,23 +49,47 @@ pub struct CreateMemberFormData {
 pub async fn add_member(
     nest: NestedPath,
     State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
     Form(form): Form<CreateMemberFormData>,
 ) -> Result<Markup, Response> {
     lettre::Address::from_str(&form.email).map_err_response(
         crate::err_responses::ErrorResponse::AlertWithPrelude("Invalid Email"),
     )?;
 
-    sqlx::query!(
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
+
+    let created = sqlx::query!(
         r#"INSERT INTO members  (first_name,    last_name,  email)
-            VALUES              ($1,            $2,         $3)"#,
+            VALUES              ($1,            $2,         $3)
+            RETURNING id, to_jsonb(members) AS "snapshot!""#,
         form.first_name,
         form.last_name,
         form.email.to_lowercase()
     )
-    .execute(&state.db_pool)
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(created.id),
+        action: "member.create",
+        after: Some(created.snapshot),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
     .await
     .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
 
+    transaction
+        .commit()
+        .await
+        .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
+
     Ok(html! {
         #"reload-list" hx-get={(nest.as_str())} hx-vals=(format!(r#"{{"search": "{}"}}"#, form.email)) hx-target="#members-list" hx-trigger="load" hx-swap="outerHTML" { progress ."progress"."mt-6" {} }
         script { "$('#modal')[0].close();" }
@@ -1,25 +1,32 @@
 use axum::{
     extract::{NestedPath, Path, State},
     response::{IntoResponse, Response},
+    Extension,
 };
-use lettre::AsyncTransport;
 use maud::{html, Markup};
 use reqwest::StatusCode;
-use rust_decimal::prelude::ToPrimitive;
 use serde::Deserialize;
+use time::OffsetDateTime;
 
 use crate::{
     components,
-    db::members::MemberDetailsRow,
-    discord::create_invite,
+    db::{
+        audit_events::{AuditEvent, AuditEventsQuery},
+        members::MemberDetailsRow,
+    },
+    discord::invites::create_invite,
     err_responses::{ErrorResponse, MapErrorResponse},
     icons,
-    send_email::{build_mailer, build_message, EmailValues},
+    send_email::{queue_email, EmailValues},
 };
 
-pub enum DiscordMembership {
-    GuildMember(serenity::model::guild::Member),
-    GlobalUser(serenity::model::user::User),
+struct InviteRow {
+    code: String,
+    reason: Option<String>,
+    status: String,
+    created_at: OffsetDateTime,
+    expires_at: Option<OffsetDateTime>,
+    account_email: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -51,6 +58,43 @@ pub async fn details(
         _ => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
     })?;
 
+    let email_aliases = sqlx::query_scalar!(
+        "SELECT email FROM member_email_aliases WHERE member_id = $1 ORDER BY replaced_on DESC",
+        member_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+
+    let invites = sqlx::query_as!(
+        InviteRow,
+        r#"SELECT invites.code, invites.reason, invites.status, invites.created_at, invites.expires_at, accounts.email AS "account_email?"
+            FROM invites
+                LEFT JOIN accounts ON accounts.id = invites.account_id
+            WHERE invites.member_id = $1
+            ORDER BY invites.created_at DESC"#,
+        member_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+
+    let history = crate::db::audit_events::search(
+        &AuditEventsQuery {
+            action: None,
+            account_id: None,
+            member_id: Some(member_id),
+            member_search: None,
+            from: None,
+            to: None,
+            count: 50,
+            offset: 0,
+        },
+        &state,
+    )
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+
     let webconnex = state
         .http_client
         .get("https://api.webconnex.com/v2/public/search/customers")
@@ -83,40 +127,11 @@ pub async fn details(
         .await
         .map_err_response(ErrorResponse::InternalServerError)?;
 
-    let discord_opt = match member.discord.and_then(|uid| uid.to_u64()) {
+    let discord_user = match member.discord {
+        Some(discord) => crate::db::discord_users::by_id(discord, &state.db_pool)
+            .await
+            .map_err_response(ErrorResponse::InternalServerError)?,
         None => None,
-        Some(uid) => {
-            let user_id = serenity::model::id::UserId::new(uid);
-            match state
-                .discord_http
-                .get_member(state.discord_guild, user_id)
-                .await
-            {
-                Ok(member) => Some(DiscordMembership::GuildMember(member)),
-                Err(_) => match state.discord_http.get_user(user_id).await {
-                    Ok(user) => Some(DiscordMembership::GlobalUser(user)),
-                    Err(_) => None,
-                },
-            }
-        }
-    };
-
-    let discord_role = match &discord_opt {
-        Some(DiscordMembership::GuildMember(member)) => {
-            match state
-                .discord_http
-                .get_guild_roles(state.discord_guild)
-                .await
-            {
-                Err(_) => None,
-                Ok(roles) => roles
-                    .iter()
-                    .filter(|r| member.roles.contains(&r.id))
-                    .max_by_key(|r| r.position)
-                    .map(ToOwned::to_owned),
-            }
-        }
-        _ => None,
     };
 
     Ok(html! {
@@ -126,9 +141,13 @@ pub async fn details(
             @else if member.cancelled { ."badge"."badge-warning" {"Cancelled"} }
             @if member.is_active == Some(true) { ."badge"."badge-success"."badge-outline" {"Active"} }
             @else { ."badge".{"badge-"(if member.cancelled || member.banned {"outline"} else {"info"})} {"Inactive"} }
+            @if let Some(plan_name) = &member.current_plan_name { ."badge"."badge-primary"."badge-outline" {(plan_name)} }
             // ."badge-info"
         }
         a ."btn"."btn-link" href={"mailto:"(member.email)} {(member.email)}
+        @if !email_aliases.is_empty() {
+            p ."text-sm"."opacity-70" {"Previously: "(email_aliases.join(", "))}
+        }
         @match member.first_payment {
             None => p {"No recorded payments"},
             Some(first_payment) => {
@@ -150,41 +169,68 @@ pub async fn details(
                 a ."btn"."btn-outline"."btn-primary" href={"https://donorbox.org/org_admin/supporters/"(db_account.id)} target="_blank" {"Donorbox ID "(db_account.id)}
             }
         }
-        @if let Some(discord) = discord_opt {
+        @if let Some(discord_user) = discord_user {
             ."card"."card-compact"."card-side"."bg-neutral"."h-24"."w-full"."max-w-sm"."mx-auto"."my-2" {
-                @match discord {
-                    DiscordMembership::GuildMember(discord_member) => {
-                        figure ."w-24" { img src=(discord_member.face()); }
-                        ."card-body" {
-                            ."card-title"."!mb-0" {
-                                (icons::discord())
-                                @if let Some(role) = discord_role {
-                                    ."badge"."badge-outline" style={"border-color:#"(role.colour.hex())"; color:#"(role.colour.hex())";"} {(role.name)}
-                                } @else { ."badge"."badge-warning" {"Guild Member, No Role"} }
+                figure ."w-24" { img src=(discord_user.avatar_url); }
+                ."card-body" {
+                    ."card-title"."!mb-0" {
+                        (icons::discord())
+                        @if !discord_user.in_guild {
+                            ."badge"."badge-error" {"Not a Guild Member"}
+                        } @else if let (Some(role_name), Some(role_colour)) = (&discord_user.top_role_name, discord_user.top_role_colour) {
+                            ."badge"."badge-outline" style={"border-color:#"(format!("{:06x}", role_colour))"; color:#"(format!("{:06x}", role_colour))";"} {(role_name)}
+                        } @else { ."badge"."badge-warning" {"Guild Member, No Role"} }
+                    }
+                    p { b {(discord_user.display_name)} br; i {"("(discord_user.username)")"} }
+                    p ."text-xs"."opacity-60" {
+                        @if let Some(joined_at) = &discord_user.joined_at { "Joined "(components::format_timestamp(joined_at))", " }
+                        "synced "(components::format_timestamp(&discord_user.synced_at))
+                    }
+                }
+            }
+        } @else if let Some(discord) = member.discord {
+            p ."text-sm"."opacity-70" {(icons::discord())" Discord user "(discord)" has not been synced yet"}
+        }
+        @if !invites.is_empty() {
+            ."divider" {"Discord Invites"}
+            table ."table"."table-sm" {
+                thead { tr { th {"Code"} th {"Issued"} th {"Expires"} th {"Status"} th {"Reason"} } }
+                tbody {
+                    @for invite in &invites {
+                        tr {
+                            td ."font-mono" {(invite.code)}
+                            td {
+                                (components::format_timestamp(&invite.created_at))
+                                @if let Some(account_email) = &invite.account_email { br; span ."text-xs"."opacity-70" {(account_email)} }
                             }
-                            p { b {(discord_member.display_name())} br; i {"("(discord_member.user.name)")"} }
-                        }
-                    },
-                    DiscordMembership::GlobalUser(discord_user) => {
-                        figure ."w-24" { img src=(discord_user.face()); }
-                        ."card-body" {
-                            ."card-title" {
-                                (icons::discord())
-                                ."badge"."badge-error" {"Not a Guild Member"}
+                            td {@if let Some(expires_at) = &invite.expires_at {(components::format_timestamp(expires_at))}}
+                            td {
+                                @match invite.status.as_str() {
+                                    "used" => ."badge"."badge-success" {"Used"},
+                                    "expired" => ."badge"."badge-ghost" {"Expired"},
+                                    "revoked" => ."badge"."badge-warning" {"Revoked"},
+                                    _ => ."badge"."badge-info" {"Pending"},
+                                }
                             }
-                            p {(discord_user.name)}
+                            td ."text-sm" {(invite.reason.as_deref().unwrap_or(""))}
                         }
                     }
                 }
             }
         }
-        @if !member.notes.is_empty() {
-            ."divider" {"Notes"}
-            pre ."w-full"."overflow-x-auto" {(member.notes.trim())}
+        ."divider" {"Notes"}
+        div id={"member_notes_"(member.id)} hx-get={(nest.as_str())"/notes/"(member.id)} hx-trigger="load" {
+            progress ."progress" {}
+        }
+        @if !history.is_empty() {
+            ."divider" {"History"}
+            (crate::admin::activity::timeline(&history))
         }
         ."divider"."mb-0" {"Actions"}
         ."*:mt-3"."*:mr-2"."*:align-bottom" {
             a href={"/admin/payments?member_search="(member.id)} ."btn"."btn-secondary"."btn-outline" {"View Payments"}
+            button ."btn"."btn-secondary"."btn-outline" onclick="openModal()" hx-get={(nest.as_str())"/edit/"(member.id)} hx-target="#modal-content" {"Edit"}
+            button ."btn"."btn-secondary"."btn-outline" onclick="openModal()" hx-get={(nest.as_str())"/merge/"(member.id)} hx-target="#modal-content" {"Merge Duplicate"}
             @if !member.banned {
                 button ."btn"."btn-secondary"."btn-outline" onclick="openModal()" hx-get={(nest.as_str())"/new_payment/"(member.id)} hx-target="#modal-content" {"Add Payment"}
                 @if member.is_active == Some(true) {
@@ -204,6 +250,7 @@ pub async fn details(
 pub async fn send_discord_email(
     State(state): State<crate::AppState>,
     Path(member_id): Path<i32>,
+    Extension(admin): Extension<crate::auth::Jwt>,
 ) -> Result<Markup, Response> {
     let email = sqlx::query_scalar!(
         r#"SELECT first_name||' '||last_name||' <'||email||'>' AS "id!" FROM members WHERE id=$1"#,
@@ -218,34 +265,49 @@ pub async fn send_discord_email(
             .await
             .map_err_response(ErrorResponse::Toast)?;
 
-    let mailer = build_mailer(&state)
+    let mut transaction = state
+        .db_pool
+        .begin()
         .await
         .map_err_response(ErrorResponse::Toast)?;
     let invite_url = create_invite(
+        Some(member_id),
+        Some(admin.account.id),
         Some(&format!("Manual send to {} from MDMA Web UI", email)),
         &state,
+        &mut *transaction,
     )
     .await
     .map_err_response(ErrorResponse::Toast)?;
-    let message = build_message(
+    queue_email(
         "discord",
-        "Psychedelic Club Discord",
         &email,
         &EmailValues {
             first_name,
             invite_url,
             ..Default::default()
         },
-        &state,
+        Some(member_id),
+        &mut transaction,
     )
     .await
     .map_err_response(ErrorResponse::Toast)?;
 
-    mailer
-        .send(message)
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(member_id),
+        action: "member.discord_invite",
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+    transaction
+        .commit()
         .await
         .map_err_response(ErrorResponse::Toast)?;
+
     Ok(html! {
-        (components::ToastAlert::Success(&format!("Sent Discord Invite to {}", email)))
+        (components::ToastAlert::Success(&format!("Queued Discord Invite to {}", email)))
     })
 }
@@ -0,0 +1,179 @@
+use std::str::FromStr;
+
+use axum::{
+    extract::{NestedPath, Path, State},
+    response::Response,
+    Extension, Form,
+};
+use maud::{html, Markup, PreEscaped};
+use serde::Deserialize;
+
+use crate::{
+    components,
+    db::{
+        audit_events::{member_snapshot, AuditEvent},
+        members::MemberRow,
+    },
+    err_responses::{ErrorResponse, MapErrorResponse},
+};
+
+pub async fn edit_form(
+    nest: NestedPath,
+    Path(member_id): Path<i32>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let member = sqlx::query_as!(MemberRow, "SELECT * FROM members WHERE id = $1", member_id)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(html! {
+        h1 ."font-bold"."text-xl" {"Edit Member: "(member.last_name)", "(member.first_name)}
+        ."form-response" {}
+        ."divider" {}
+        form hx-post={(nest.as_str())"/edit/"(member.id)} hx-target="previous .form-response" hx-indicator="#modal-loading" {
+            ."form-control"."w-full" {
+                ."label" {
+                    span ."label-text" {"First Name"}
+                }
+                input type="text" name="first_name" required value=(member.first_name) ."input"."input-bordered"."w-full";
+            }
+            ."form-control"."w-full" {
+                ."label" {
+                    span ."label-text" {"Last Name"}
+                }
+                input type="text" name="last_name" required value=(member.last_name) ."input"."input-bordered"."w-full";
+            }
+            ."form-control"."w-full" {
+                ."label" {
+                    span ."label-text" {"Email"}
+                }
+                input type="email" name="email" required value=(member.email) ."input"."input-bordered"."w-full";
+            }
+            ."form-control"."mt-4" { button ."btn"."btn-outline"."btn-primary"."w-1/2"."mx-auto" {"SUBMIT"} }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct EditMemberFormData {
+    first_name: String,
+    last_name: String,
+    email: String,
+}
+
+pub async fn edit_member(
+    Path(member_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<EditMemberFormData>,
+) -> Result<Markup, Response> {
+    let first_name = form.first_name.trim();
+    let last_name = form.last_name.trim();
+    let email = form.email.trim().to_lowercase();
+
+    if first_name.is_empty() || last_name.is_empty() {
+        return Err("First and last name are required").map_err_response(ErrorResponse::Alert);
+    }
+    lettre::Address::from_str(&email)
+        .map_err_response(ErrorResponse::AlertWithPrelude("Invalid Email"))?;
+
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    let old_email = sqlx::query_scalar!(
+        "SELECT email FROM members WHERE id = $1 FOR UPDATE",
+        member_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+    let before = member_snapshot(member_id, &mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    if old_email != email {
+        let owner = sqlx::query_scalar!("SELECT member_id_by_email($1)", email)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err_response(ErrorResponse::Alert)?;
+        if let Some(owner_id) = owner.filter(|id| *id != member_id) {
+            return Err(format!(
+                "{} is already used by member #{}. Merge the records instead.",
+                email, owner_id
+            ))
+            .map_err_response(ErrorResponse::Alert);
+        }
+
+        sqlx::query!(
+            "DELETE FROM member_email_aliases WHERE email = $1 AND member_id = $2",
+            email,
+            member_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+        sqlx::query!(
+            r#"INSERT INTO member_email_aliases (email, member_id)
+                VALUES (LOWER($1), $2)
+                ON CONFLICT (email) DO UPDATE SET member_id = excluded.member_id, replaced_on = excluded.replaced_on"#,
+            old_email,
+            member_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+    }
+
+    let after = sqlx::query_scalar!(
+        r#"UPDATE members
+            SET first_name = $2, last_name = $3, email = $4
+            WHERE id = $1
+            RETURNING to_jsonb(members) AS "after!""#,
+        member_id,
+        first_name,
+        last_name,
+        email
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            ErrorResponse::Alert.transform(format!("{} is already used by another member", email))
+        }
+        _ => ErrorResponse::Alert.transform(err),
+    })?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(member_id),
+        action: "member.edit",
+        before: Some(before),
+        after: Some(after),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(html! {
+        div hx-swap-oob={"innerHTML:#user_details_"(member_id)} {
+            progress ."progress"."htmx-indicator" {
+                script {(PreEscaped(format!("
+                        $('#modal')[0].close();
+                        htmx.trigger('#user_details_trigger_{}', 'change', {{}});
+                    ", member_id)))}
+            }
+        }
+        (components::ToastAlert::Success("Member Updated Successfully"))
+    })
+}
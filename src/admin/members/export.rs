@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Query, State},
+    response::Response,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    admin::csv_export::{csv_response, optional},
+    db::members::{MemberDetailsRow, MembersQuery},
+};
+
+const HEADER: &[&str] = &[
+    "id",
+    "first_name",
+    "last_name",
+    "email",
+    "created_on",
+    "discord",
+    "cancelled",
+    "banned",
+    "generation_id",
+    "generation_name",
+    "first_payment",
+    "consecutive_since",
+    "consecutive_until",
+    "is_active",
+    "current_plan",
+];
+
+fn member_record(member: MemberDetailsRow) -> Vec<String> {
+    vec![
+        member.id.to_string(),
+        member.first_name,
+        member.last_name,
+        member.email,
+        member.created_on.to_string(),
+        optional(member.discord),
+        member.cancelled.to_string(),
+        member.banned.to_string(),
+        optional(member.generation_id),
+        member.generation_name.unwrap_or_default(),
+        optional(member.first_payment),
+        optional(member.consecutive_since),
+        optional(member.consecutive_until),
+        optional(member.is_active),
+        member.current_plan_name.unwrap_or_default(),
+    ]
+}
+
+pub async fn members_csv(
+    Query(params): Query<MembersQuery>,
+    State(state): State<crate::AppState>,
+) -> Response {
+    csv_response(
+        &format!("members-{}.csv", OffsetDateTime::now_utc().date()),
+        HEADER,
+        crate::db::members::export(params, state),
+        member_record,
+    )
+}
@@ -0,0 +1,257 @@
+use axum::{
+    extract::{NestedPath, Path, State},
+    response::Response,
+    Extension, Form,
+};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+use crate::{
+    components,
+    db::{
+        audit_events::{member_snapshot, AuditEvent},
+        members::MemberRow,
+    },
+    err_responses::{ErrorResponse, MapErrorResponse},
+    icons,
+};
+
+pub async fn merge_form(
+    nest: NestedPath,
+    Path(member_id): Path<i32>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let member = sqlx::query_as!(MemberRow, "SELECT * FROM members WHERE id = $1", member_id)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(html! {
+        h1 ."font-bold"."text-xl" {"Merge Into: "(member.last_name)", "(member.first_name)}
+        h2 ."text-lg" {(member.email)}
+        ."form-response" {}
+        ."divider" {}
+        form ."mt-3" hx-post={(nest.as_str())"/merge/"(member.id)} hx-target="previous .form-response" hx-indicator="#modal-loading" {
+            ."form-control"."w-full" {
+                ."label" {
+                    span ."label-text" {"Duplicate Member (ID or Email)"}
+                }
+                input type="text" name="duplicate" required ."input"."input-bordered"."w-full";
+                ."alert"."alert-warning"."mt-4"."w-full" role="warning" {
+                    (icons::warning())
+                    span {"Payments, Discord account, notes and emails of the duplicate will be moved onto this member, and the duplicate record will be deleted. This cannot be undone."}
+                }
+                ."form-control"."mt-4" { button ."btn"."btn-outline"."btn-primary"."w-1/2"."mx-auto" {(icons::warning())" MERGE"} }
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct MergeFormData {
+    duplicate: String,
+}
+
+pub async fn merge_member(
+    nest: NestedPath,
+    Path(member_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<MergeFormData>,
+) -> Result<Markup, Response> {
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    let duplicate_id = match form.duplicate.trim().parse::<i32>() {
+        Ok(id) => Some(id),
+        Err(_) => sqlx::query_scalar!("SELECT member_id_by_email($1)", form.duplicate.trim())
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err_response(ErrorResponse::Alert)?,
+    }
+    .ok_or("Could not find the duplicate member")
+    .map_err_response(ErrorResponse::Alert)?;
+
+    if duplicate_id == member_id {
+        return Err("Cannot merge a member into itself").map_err_response(ErrorResponse::Alert);
+    }
+
+    let survivor = sqlx::query_as!(
+        MemberRow,
+        "SELECT * FROM members WHERE id = $1 FOR UPDATE",
+        member_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+    let duplicate = sqlx::query_as!(
+        MemberRow,
+        "SELECT * FROM members WHERE id = $1 FOR UPDATE",
+        duplicate_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::AlertWithPrelude(
+        "Could not find the duplicate member",
+    ))?;
+
+    sqlx::query!(
+        "UPDATE payments SET member_id = $1 WHERE member_id = $2",
+        member_id,
+        duplicate_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!(
+        "UPDATE member_email_aliases SET member_id = $1 WHERE member_id = $2",
+        member_id,
+        duplicate_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!(
+        "UPDATE member_notes SET member_id = $1 WHERE member_id = $2",
+        member_id,
+        duplicate_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!(
+        "UPDATE invites SET member_id = $1 WHERE member_id = $2",
+        member_id,
+        duplicate_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+    sqlx::query!(
+        "UPDATE email_outbox SET member_id = $1 WHERE member_id = $2",
+        member_id,
+        duplicate_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!(
+        "UPDATE audit_events SET member_id = $1 WHERE member_id = $2",
+        member_id,
+        duplicate_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    let before = member_snapshot(member_id, &mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+    let duplicate_snapshot = member_snapshot(duplicate_id, &mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!(
+        "DELETE FROM consecutive_since_cached WHERE member_id = $1",
+        duplicate_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!("DELETE FROM members WHERE id = $1", duplicate_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!(
+        r#"INSERT INTO member_email_aliases (email, member_id)
+            VALUES (LOWER($1), $2)
+            ON CONFLICT (email) DO UPDATE SET member_id = excluded.member_id, replaced_on = excluded.replaced_on"#,
+        duplicate.email,
+        member_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    let mut reason = format!(
+        "Merged member #{} ({}, {} <{}>)",
+        duplicate.id, duplicate.last_name, duplicate.first_name, duplicate.email
+    );
+    if let (Some(kept), Some(dropped)) = (survivor.discord, duplicate.discord) {
+        if kept != dropped {
+            reason += &format!(", discarded Discord ID {}", dropped);
+        }
+    }
+
+    let after = sqlx::query_scalar!(
+        r#" UPDATE members
+            SET
+                discord = COALESCE(discord, $2),
+                cancelled = cancelled OR $3,
+                banned = banned OR $4
+            WHERE id = $1
+            RETURNING to_jsonb(members) AS "after!""#,
+        member_id,
+        duplicate.discord,
+        duplicate.cancelled,
+        duplicate.banned
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(member_id),
+        action: "member.merge_duplicate",
+        reason: Some(&reason),
+        before: Some(duplicate_snapshot),
+        after: None,
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(member_id),
+        action: "member.merge",
+        reason: Some(&reason),
+        before: Some(before),
+        after: Some(after),
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    sqlx::query!(
+        r#"INSERT INTO consecutive_since_cached (member_id, cached_value)
+            VALUES ($1, consecutive_since($1))
+            ON CONFLICT (member_id) DO UPDATE SET cached_value = excluded.cached_value"#,
+        member_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(html! {
+        #"reload-list" hx-get={(nest.as_str())} hx-vals=(format!(r#"{{"search": "{}"}}"#, survivor.email)) hx-target="#members-list" hx-trigger="load" hx-swap="outerHTML" { progress ."progress"."mt-6" {} }
+        script { "$('#modal')[0].close();" }
+
+        (components::ToastAlert::Success(&format!("Merged member #{} into {}, {}", duplicate.id, survivor.last_name, survivor.first_name)))
+    })
+}
@@ -6,7 +6,11 @@ use axum::{
 mod cancel_ban;
 mod create_member;
 mod details;
+mod edit_member;
+pub mod export;
+mod merge_members;
 mod new_payment;
+mod notes;
 mod search;
 
 pub fn router(state: crate::AppState) -> Router {
@@ -26,6 +30,23 @@ pub fn router(state: crate::AppState) -> Router {
             "/create",
             get(create_member::member_form).post(create_member::add_member),
         )
+        .route(
+            "/edit/{member_id}",
+            get(edit_member::edit_form).post(edit_member::edit_member),
+        )
+        .route(
+            "/merge/{member_id}",
+            get(merge_members::merge_form).post(merge_members::merge_member),
+        )
+        .route(
+            "/notes/{member_id}",
+            get(notes::notes_list).post(notes::add_note),
+        )
+        .route(
+            "/notes/{member_id}/{note_id}",
+            post(notes::edit_note).delete(notes::delete_note),
+        )
+        .route("/notes/{member_id}/{note_id}/edit", get(notes::edit_form))
         .route(
             "/cancel/{member_id}",
             get(cancel_ban::cancel_form).post(cancel_ban::cancel_member),
@@ -1,15 +1,21 @@
 use axum::{
     extract::{NestedPath, Path, State},
     response::{IntoResponse, Response},
-    Form,
+    Extension,
 };
+use axum_extra::extract::Form;
 use maud::{html, Markup, PreEscaped};
 use reqwest::StatusCode;
-use rust_decimal::Decimal;
-use serde::Deserialize;
-use time::Date;
 
-use crate::{components, db::members::MemberRow, err_responses::MapErrorResponse};
+use crate::{
+    admin::config::plans::plan_select,
+    components,
+    db::{
+        members::MemberRow,
+        payments::{NewPayment, PAYMENT_METHODS},
+    },
+    err_responses::MapErrorResponse,
+};
 
 pub async fn payment_form(
     nest: NestedPath,
@@ -27,6 +33,7 @@ pub async fn payment_form(
         sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, err.to_string()).into_response(),
         _ => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
     })?;
+    let plans = crate::db::plans::active(&state.db_pool).await;
 
     Ok(html! {
         h1 ."font-bold"."text-xl" {"Add Payment: "(member.last_name)", "(member.first_name)}
@@ -34,17 +41,21 @@ pub async fn payment_form(
         ."form-response" {}
         ."divider" {}
         form ."mt-3" hx-post={(nest.as_str())"/new_payment/"(member.id)} hx-target="previous .form-response" hx-indicator="#modal-loading" {
+            @if !plans.is_empty() {
+                ."form-control" {
+                    label ."label"."cursor-pointer" {
+                        span ."label-text" {"Plan"}
+                        (plan_select(&plans, None))
+                    }
+                }
+            }
             ."form-control" {
                 label ."label"."cursor-pointer" {
                     span ."label-text" {"Payment Method / Reason"}
                     select name="payment_method" required ."select"."select-primary" {
-                        option value="cash" {"Cash"}
-                        option value="card" {"Card"}
-                        option value="volunteer" {"Volunteering"}
-                        option value="grace-period" {"Grace Period"}
-                        option value="ethics-committee" {"Ethics Committee"}
-                        option value="exec-board" {"Executive Board"}
-                        option value="other" {"Other"}
+                        @for (method, label) in PAYMENT_METHODS {
+                            option value=(method) {(label)}
+                        }
                     }
                 }
             }
@@ -78,35 +89,21 @@ pub async fn payment_form(
     })
 }
 
-#[derive(Deserialize)]
-pub struct NewPaymentFormData {
-    payment_method: String,
-    amount_paid: Option<Decimal>,
-    transaction_id: Option<i32>,
-    effective_on: Date,
-    duration_months: i32,
-    notes: Option<String>,
-}
-
 pub async fn add_payment(
     State(state): State<crate::AppState>,
     Path(user_id): Path<i32>,
-    Form(form): Form<NewPaymentFormData>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<NewPayment>,
 ) -> Result<Markup, Response> {
-    sqlx::query_scalar!(
-        r#"INSERT INTO payments (member_id, effective_on, duration_months, amount_paid, payment_method, transaction_id, notes)
-            VALUES              ($1,        $2,           $3,              $4,          $5,             $6,             $7)
-            RETURNING id"#,
-        user_id,
-        form.effective_on,
-        form.duration_months,
-        form.amount_paid.unwrap_or(Decimal::ZERO),
-        form.payment_method,
-        form.transaction_id,
-        form.notes
-    ).fetch_one(&state.db_pool)
-    .await
-    .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
+    if let Some(plan_id) = form.plan_id {
+        crate::db::plans::check_duration(plan_id, form.duration_months, None, &state.db_pool)
+            .await
+            .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
+    }
+
+    crate::db::payments::create(user_id, admin.account.id, &form, &state.db_pool)
+        .await
+        .map_err_response(crate::err_responses::ErrorResponse::Alert)?;
 
     Ok(html! {
         div hx-swap-oob={"innerHTML:#user_details_"(user_id)} {
@@ -0,0 +1,304 @@
+use axum::{
+    extract::{NestedPath, Path, State},
+    response::Response,
+    Extension, Form,
+};
+use maud::{html, Markup};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{
+    components,
+    db::audit_events::AuditEvent,
+    err_responses::{ErrorResponse, MapErrorResponse},
+};
+
+struct MemberNoteRow {
+    id: i32,
+    parent_id: Option<i32>,
+    account_id: Option<i32>,
+    author: Option<String>,
+    body: String,
+    created_at: OffsetDateTime,
+    updated_at: Option<OffsetDateTime>,
+}
+
+fn note_card(nest: &str, member_id: i32, note: &MemberNoteRow, admin_id: i32) -> Markup {
+    html! {
+        ."card"."card-compact"."bg-base-100"."my-2" { ."card-body" {
+            ."flex"."flex-wrap"."gap-2"."items-center"."text-sm" {
+                b {(note.author.as_deref().unwrap_or("Imported"))}
+                span ."opacity-70" {(components::format_timestamp(&note.created_at))}
+                @if let Some(updated_at) = &note.updated_at {
+                    span ."opacity-70"."italic" {"(edited "(components::format_timestamp(updated_at))")"}
+                }
+                @if note.account_id == Some(admin_id) {
+                    ."ml-auto"."*:ml-1" {
+                        button ."btn"."btn-xs"."btn-ghost" hx-get={(nest)"/notes/"(member_id)"/"(note.id)"/edit"} hx-target="next .member-note-body" {"Edit"}
+                        button ."btn"."btn-xs"."btn-ghost"."text-error" hx-delete={(nest)"/notes/"(member_id)"/"(note.id)} hx-target={"#member_notes_"(member_id)}
+                            hx-confirm="Delete this note and its replies?" {"Delete"}
+                    }
+                }
+            }
+            p ."member-note-body"."whitespace-pre-wrap" {(note.body)}
+        }}
+    }
+}
+
+fn note_form(nest: &str, member_id: i32, parent_id: Option<i32>) -> Markup {
+    html! {
+        form hx-post={(nest)"/notes/"(member_id)} hx-target={"#member_notes_"(member_id)} ."flex"."gap-2"."items-end"."my-2" {
+            @if let Some(parent_id) = parent_id {
+                input type="hidden" name="parent_id" value=(parent_id);
+            }
+            textarea name="body" required placeholder=(if parent_id.is_some() {"Reply"} else {"Add a note"}) ."textarea"."textarea-bordered"."grow" {}
+            button ."btn"."btn-outline"."btn-primary" {(if parent_id.is_some() {"REPLY"} else {"ADD NOTE"})}
+        }
+    }
+}
+
+async fn render_notes(
+    nest: &str,
+    member_id: i32,
+    admin: &crate::auth::Jwt,
+    state: &crate::AppState,
+) -> Result<Markup, Response> {
+    let notes = sqlx::query_as!(
+        MemberNoteRow,
+        r#"SELECT
+                member_notes.id,
+                parent_id,
+                account_id,
+                accounts.email AS "author?",
+                body,
+                created_at,
+                updated_at
+            FROM member_notes
+                LEFT JOIN accounts ON accounts.id = account_id
+            WHERE member_id = $1
+            ORDER BY created_at ASC, member_notes.id ASC"#,
+        member_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(html! {
+        @for note in notes.iter().filter(|note| note.parent_id.is_none()) {
+            (note_card(nest, member_id, note, admin.account.id))
+            ."ml-8" {
+                @for reply in notes.iter().filter(|reply| reply.parent_id == Some(note.id)) {
+                    (note_card(nest, member_id, reply, admin.account.id))
+                }
+                details ."text-sm" {
+                    summary ."cursor-pointer"."opacity-70" {"Reply"}
+                    (note_form(nest, member_id, Some(note.id)))
+                }
+            }
+        }
+        (note_form(nest, member_id, None))
+    })
+}
+
+pub async fn notes_list(
+    nest: NestedPath,
+    Path(member_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+) -> Result<Markup, Response> {
+    render_notes(nest.as_str(), member_id, &admin, &state).await
+}
+
+#[derive(Deserialize)]
+pub struct NoteFormData {
+    body: String,
+    parent_id: Option<i32>,
+}
+
+pub async fn add_note(
+    nest: NestedPath,
+    Path(member_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<NoteFormData>,
+) -> Result<Markup, Response> {
+    if form.body.trim().is_empty() {
+        return Err("Note cannot be empty").map_err_response(ErrorResponse::Toast);
+    }
+
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    let note = sqlx::query_scalar!(
+        r#"INSERT INTO member_notes (member_id, parent_id, account_id, body)
+            SELECT                   $1,        $2,        $3,         $4
+            WHERE $2::INT IS NULL
+                OR EXISTS (SELECT 1 FROM member_notes WHERE id = $2 AND member_id = $1 AND parent_id IS NULL)
+            RETURNING to_jsonb(member_notes) AS "note!""#,
+        member_id,
+        form.parent_id,
+        admin.account.id,
+        form.body.trim()
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?
+    .ok_or("Cannot reply to that note")
+    .map_err_response(ErrorResponse::Toast)?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(member_id),
+        action: "note.create",
+        after: Some(note),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    render_notes(nest.as_str(), member_id, &admin, &state).await
+}
+
+pub async fn edit_form(
+    nest: NestedPath,
+    Path((member_id, note_id)): Path<(i32, i32)>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let body = sqlx::query_scalar!(
+        "SELECT body FROM member_notes WHERE id = $1 AND member_id = $2",
+        note_id,
+        member_id
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+
+    Ok(html! {
+        form hx-post={(nest.as_str())"/notes/"(member_id)"/"(note_id)} hx-target={"#member_notes_"(member_id)} ."flex"."gap-2"."items-end" {
+            textarea name="body" required ."textarea"."textarea-bordered"."grow" {(body)}
+            button ."btn"."btn-outline"."btn-primary" {"SAVE"}
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct EditNoteFormData {
+    body: String,
+}
+
+pub async fn edit_note(
+    nest: NestedPath,
+    Path((member_id, note_id)): Path<(i32, i32)>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<EditNoteFormData>,
+) -> Result<Markup, Response> {
+    if form.body.trim().is_empty() {
+        return Err("Note cannot be empty").map_err_response(ErrorResponse::Toast);
+    }
+
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    let before = sqlx::query_scalar!(
+        r#"SELECT to_jsonb(member_notes) AS "note!" FROM member_notes
+            WHERE id = $1 AND member_id = $2 AND account_id = $3
+            FOR UPDATE"#,
+        note_id,
+        member_id,
+        admin.account.id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?
+    .ok_or("Only the author can edit this note")
+    .map_err_response(ErrorResponse::Toast)?;
+
+    let after = sqlx::query_scalar!(
+        r#"UPDATE member_notes
+            SET body = $2, updated_at = NOW()
+            WHERE id = $1
+            RETURNING to_jsonb(member_notes) AS "note!""#,
+        note_id,
+        form.body.trim()
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(member_id),
+        action: "note.edit",
+        before: Some(before),
+        after: Some(after),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    render_notes(nest.as_str(), member_id, &admin, &state).await
+}
+
+pub async fn delete_note(
+    nest: NestedPath,
+    Path((member_id, note_id)): Path<(i32, i32)>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+) -> Result<Markup, Response> {
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    let before = sqlx::query_scalar!(
+        r#"DELETE FROM member_notes
+            WHERE id = $1 AND member_id = $2 AND account_id = $3
+            RETURNING to_jsonb(member_notes) AS "note!""#,
+        note_id,
+        member_id,
+        admin.account.id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?
+    .ok_or("Only the author can delete this note")
+    .map_err_response(ErrorResponse::Toast)?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(member_id),
+        action: "note.delete",
+        before: Some(before),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    render_notes(nest.as_str(), member_id, &admin, &state).await
+}
@@ -34,6 +34,13 @@ pub async fn members_list(
     .fetch_all(&state.db_pool)
     .await
     .unwrap();
+    let plan_options = sqlx::query_as!(
+        SelectIdOption,
+        r#"SELECT id, name AS "description!" FROM plans ORDER BY archived, name"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
 
     html! {
     #"members-list" ."w-full"."max-w-xl"."mx-auto" {
@@ -81,6 +88,17 @@ pub async fn members_list(
                             }
                         }
                     }
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Current Plan"}
+                            select name="plan_id" ."select"."select-bordered" {
+                                option value="-1" {"(Any Plan)"}
+                                @for plan in plan_options {
+                                    option value=(plan.id) selected[plan.id==params.plan_id] {(plan.description)}
+                                }
+                            }
+                        }
+                    }
                     ."divider" {"Sort Results"}
                     ."form-control" {
                         label ."label"."cursor-pointer" {
@@ -174,5 +192,7 @@ pub async fn search_results(
             }
             (pagebtn(next, "Next"))
         }
+        a href={(nest.as_str())"/export.csv?"(serde_html_form::to_string(&params).unwrap_or_default())} download
+            ."btn"."btn-outline"."btn-secondary"."btn-sm"."block"."w-fit"."mx-auto"."mt-3" {(icons::download())" Export CSV"}
     })
 }
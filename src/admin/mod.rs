@@ -10,10 +10,14 @@ use maud::{html, Markup};
 
 use crate::components;
 
+mod activity;
 mod bulk_update;
+mod campaigns;
 mod config;
+mod csv_export;
 mod generations;
 mod members;
+mod outbox;
 mod payments;
 
 fn home(nest: &str, load_main: Option<Uri>) -> Markup {
@@ -24,6 +28,9 @@ fn home(nest: &str, load_main: Option<Uri>) -> Markup {
                 li {a hx-get={(nest)"/payments"}        hx-target="main" hx-push-url="true" {"Payments"}}
                 li {a hx-get={(nest)"/generations"}     hx-target="main" hx-push-url="true" {"Generations"}}
                 li {a hx-get={(nest)"/bulk_update"}     hx-target="main" hx-push-url="true" {"Bulk Update"}}
+                li {a hx-get={(nest)"/activity"}        hx-target="main" hx-push-url="true" {"Activity"}}
+                li {a hx-get={(nest)"/campaigns"}       hx-target="main" hx-push-url="true" {"Campaigns"}}
+                li {a hx-get={(nest)"/outbox"}          hx-target="main" hx-push-url="true" {"Outbox"}}
                 li {a hx-get={(nest)"/config"}          hx-target="main" hx-push-url="true" {"Settings"}}
             }
             ul ."menu"."menu-horizontal"."navbar-end" {
@@ -67,10 +74,21 @@ pub fn router(state: crate::AppState) -> Router {
             post(bulk_update::submit_donorbox_bulk_update),
         )
         .with_state(state.clone())
+        .nest("/activity", activity::router(state.clone()))
+        .nest("/campaigns", campaigns::router(state.clone()))
         .nest("/config", config::router(state.clone()))
         .nest("/members", members::router(state.clone()))
+        .nest("/outbox", outbox::router(state.clone()))
         .nest("/payments", payments::router(state.clone()))
         .layer(middleware::from_fn(handle_nonhtmx_request))
+        .route(
+            "/members/export.csv",
+            get(members::export::members_csv).with_state(state.clone()),
+        )
+        .route(
+            "/payments/export.csv",
+            get(payments::export::payments_csv).with_state(state.clone()),
+        )
         .route("/", get(home_no_contents))
         .layer(middleware::from_fn_with_state(
             state.clone(),
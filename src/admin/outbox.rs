@@ -0,0 +1,216 @@
+use axum::{
+    extract::{NestedPath, Path, State},
+    response::Response,
+    routing::{get, post},
+    Extension, Router,
+};
+use axum_extra::extract::Query;
+use maud::{html, Markup};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{
+    components,
+    db::audit_events::AuditEvent,
+    err_responses::{ErrorResponse, MapErrorResponse},
+    send_email::outbox,
+};
+
+const STATUSES: &[(&str, &str)] = &[("queued", "Queued"), ("sent", "Sent"), ("failed", "Failed")];
+
+struct OutboxRow {
+    id: i32,
+    member_id: Option<i32>,
+    member_name: Option<String>,
+    email_key: String,
+    to_address: String,
+    subject: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    created_at: OffsetDateTime,
+    next_attempt_at: OffsetDateTime,
+    sent_at: Option<OffsetDateTime>,
+}
+
+async fn outbox_rows(
+    id: Option<i32>,
+    status: Option<&str>,
+    db_pool: &sqlx::PgPool,
+) -> Result<Vec<OutboxRow>, sqlx::Error> {
+    sqlx::query_as!(
+        OutboxRow,
+        r#"SELECT
+                email_outbox.id,
+                member_id,
+                members.first_name || ' ' || members.last_name AS "member_name?",
+                email_key,
+                to_address,
+                subject,
+                status,
+                attempts,
+                last_error,
+                created_at,
+                next_attempt_at,
+                sent_at
+            FROM email_outbox
+                LEFT JOIN members ON members.id = member_id
+            WHERE
+                ($1::INT IS NULL OR email_outbox.id = $1)
+                AND ($2::TEXT IS NULL OR status = $2)
+            ORDER BY email_outbox.id DESC
+            LIMIT 200"#,
+        id,
+        status
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+async fn outbox_row(id: i32, db_pool: &sqlx::PgPool) -> Result<OutboxRow, sqlx::Error> {
+    outbox_rows(Some(id), None, db_pool)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)
+}
+
+fn render_row(nest: &str, email: &OutboxRow) -> Markup {
+    html! {
+        tr {
+            td ."whitespace-nowrap" {(components::format_timestamp(&email.created_at))}
+            td {
+                (email.to_address)
+                @if let Some(member_name) = &email.member_name {
+                    ."text-xs"."opacity-60" {(member_name)}
+                }
+            }
+            td {
+                (email.subject)
+                ."text-xs"."opacity-60" {(email.email_key)}
+            }
+            td {
+                @match email.status.as_str() {
+                    "sent" => ."badge"."badge-success" {"Sent"},
+                    "failed" => ."badge"."badge-error" {"Failed"},
+                    _ => ."badge"."badge-warning" {"Queued"},
+                }
+            }
+            td ."text-sm" {
+                (email.attempts)" attempt(s)"
+                @if let Some(sent_at) = &email.sent_at {
+                    ."text-xs"."opacity-60" {"Sent "(components::format_timestamp(sent_at))}
+                } @else if email.status == "queued" && email.attempts > 0 {
+                    ."text-xs"."opacity-60" {"Retrying "(components::format_timestamp(&email.next_attempt_at))}
+                }
+                @if let Some(last_error) = &email.last_error {
+                    ."text-xs"."text-error"."max-w-xs"."break-words" {(last_error)}
+                }
+            }
+            td {
+                @if email.status != "queued" {
+                    button ."btn"."btn-sm"."btn-outline"."btn-secondary" hx-post={(nest)"/"(email.id)"/resend"} hx-target="closest tr" hx-swap="outerHTML"
+                        hx-confirm={"Send this email to "(email.to_address)" again?"} {"RESEND"}
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OutboxQuery {
+    status: Option<String>,
+}
+
+async fn outbox_list(
+    nest: NestedPath,
+    Query(params): Query<OutboxQuery>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let status = params.status.filter(|status| !status.is_empty());
+    let emails = outbox_rows(None, status.as_deref(), &state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
+
+    Ok(html! { #"outbox_list" ."w-full"."max-w-6xl"."mx-auto" {
+        ."tabs"."tabs-boxed"."w-fit"."mx-auto" role="tablist" {
+            a role="tab" ."tab".tab-active[status.is_none()] hx-get=(nest.as_str()) hx-target="main" hx-push-url="true" {"All"}
+            @for (value, label) in STATUSES {
+                a role="tab" ."tab".tab-active[status.as_deref() == Some(*value)]
+                    hx-get={(nest.as_str())"?status="(value)} hx-target="main" hx-push-url="true" {(label)}
+            }
+        }
+        p ."text-center"."text-sm"."opacity-70"."mt-2" {"Delivering via "(state.mail_transport.description())}
+        ."divider" {}
+        @if emails.is_empty() {
+            p ."text-center"."opacity-70" {"No emails."}
+        } @else {
+            ."overflow-x-auto" { table ."table"."table-zebra"."table-auto" {
+                thead { tr {
+                    th {"Queued"}
+                    th {"To"}
+                    th {"Subject"}
+                    th {"Status"}
+                    th {"Delivery"}
+                    th {}
+                }}
+                tbody {
+                    @for email in &emails { (render_row(nest.as_str(), email)) }
+                }
+            }}
+        }
+    } })
+}
+
+async fn resend_email(
+    nest: NestedPath,
+    Path(id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+) -> Result<Markup, Response> {
+    let before = outbox_row(id, &state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+    outbox::resend(id, &mut *transaction)
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: before.member_id,
+        action: "email.resend",
+        before: Some(serde_json::json!({
+            "email": id,
+            "status": before.status,
+            "attempts": before.attempts,
+        })),
+        after: Some(serde_json::json!({ "email": id, "status": "queued", "attempts": 0 })),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Toast)?;
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+
+    let email = outbox_row(id, &state.db_pool)
+        .await
+        .map_err_response(ErrorResponse::Toast)?;
+    Ok(html! {
+        (render_row(nest.as_str(), &email))
+        (components::ToastAlert::Success(&format!("Queued email to {} again", email.to_address)))
+    })
+}
+
+pub fn router(state: crate::AppState) -> Router {
+    Router::new()
+        .route("/", get(outbox_list))
+        .route("/{id}/resend", post(resend_email))
+        .with_state(state.clone())
+}
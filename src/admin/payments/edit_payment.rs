@@ -0,0 +1,376 @@
+use axum::{
+    extract::{NestedPath, Path, State},
+    response::Response,
+    Extension,
+};
+use axum_extra::extract::Form;
+use maud::{html, Markup, PreEscaped};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use time::Date;
+
+use crate::{
+    admin::config::plans::plan_select,
+    components,
+    db::{
+        audit_events::AuditEvent,
+        payments::{PAYMENT_METHODS, WEBHOOK_PAYMENT_METHODS},
+    },
+    err_responses::{ErrorResponse, MapErrorResponse},
+    icons,
+};
+
+use super::search::payment_row;
+
+fn payment_header(payment: &crate::db::payments::PaymentRow) -> Markup {
+    html! {
+        h2 ."text-lg" {(payment.last_name)", "(payment.first_name)" <"(payment.email)">"}
+        p ."opacity-70" {
+            "$"(payment.amount_paid.round_dp(2))" via "(payment.payment_method.as_deref().unwrap_or("unknown"))
+            " effective "(payment.effective_on)" for "(payment.duration_months)" mo."
+        }
+    }
+}
+
+async fn updated_row(
+    nest: &str,
+    payment_id: i32,
+    state: &crate::AppState,
+    message: &str,
+) -> Markup {
+    let row = crate::db::payments::by_id(payment_id, state).await.ok();
+
+    html! {
+        @if let Some(payment) = row {
+            template { (payment_row(nest, &payment, true)) }
+        }
+        script {(PreEscaped("$('#modal')[0].close();"))}
+        (components::ToastAlert::Success(message))
+    }
+}
+
+pub async fn edit_form(
+    nest: NestedPath,
+    Path(payment_id): Path<i32>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let payment = crate::db::payments::by_id(payment_id, &state)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+    let plans = crate::db::plans::active_or_current(payment.plan_id, &state.db_pool).await;
+
+    Ok(html! {
+        h1 ."font-bold"."text-xl" {"Edit Payment #"(payment.id)}
+        (payment_header(&payment))
+        ."form-response" {}
+        ."divider" {}
+        form ."mt-3" hx-post={(nest.as_str())"/edit/"(payment.id)} hx-target="previous .form-response" hx-indicator="#modal-loading" {
+            ."form-control" {
+                label ."label"."cursor-pointer" {
+                    span ."label-text" {"Plan"}
+                    (plan_select(&plans, payment.plan_id))
+                }
+            }
+            ."form-control" {
+                label ."label"."cursor-pointer" {
+                    span ."label-text" {"Payment Method / Reason"}
+                    input type="text" name="payment_method" list="payment_methods" value=[&payment.payment_method] ."input"."input-bordered";
+                    datalist #"payment_methods" {
+                        @for method in PAYMENT_METHODS.iter().map(|(method, _)| method).chain(WEBHOOK_PAYMENT_METHODS) {
+                            option value=(method) {}
+                        }
+                    }
+                }
+            }
+            ."form-control" {
+                label ."label"."cursor-pointer" {
+                    span ."label-text" {"Amount Paid"}
+                    input type="number" name="amount_paid" required min="0" step="any" value=(payment.amount_paid.round_dp(2)) ."input"."input-bordered";
+                }
+            }
+            ."form-control" {
+                label ."label"."cursor-pointer" {
+                    span ."label-text" {"Effective On"}
+                    input type="date" name="effective_on" required value=(payment.effective_on) ."input"."input-bordered";
+                }
+            }
+            ."form-control" {
+                label ."label"."cursor-pointer" {
+                    span ."label-text" {"Duration (Months)"}
+                    input type="number" name="duration_months" required min="1" step="1" value=(payment.duration_months) ."input"."input-bordered";
+                }
+            }
+            ."form-control" {
+                label ."label"."cursor-pointer" {
+                    span ."label-text" {"Notes"}
+                    textarea name="notes" placeholder="Notes" ."textarea"."textarea-bordered" {(payment.notes.as_deref().unwrap_or_default())}
+                }
+            }
+            ."form-control"."w-full" {
+                ."label" { span ."label-text" {"Reason for Change"} }
+                input type="text" name="reason" required ."input"."input-bordered"."w-full";
+            }
+            ."form-control"."mt-4" { button ."btn"."btn-outline"."btn-primary"."w-1/2"."mx-auto" {"SUBMIT"} }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct EditPaymentFormData {
+    payment_method: Option<String>,
+    amount_paid: Decimal,
+    effective_on: Date,
+    duration_months: i32,
+    plan_id: Option<i32>,
+    notes: Option<String>,
+    reason: String,
+}
+
+pub async fn edit_payment(
+    nest: NestedPath,
+    Path(payment_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<EditPaymentFormData>,
+) -> Result<Markup, Response> {
+    if form.reason.trim().is_empty() {
+        return Err("A reason is required").map_err_response(ErrorResponse::Alert);
+    }
+    if form.amount_paid.is_sign_negative() {
+        return Err("Invalid amount paid").map_err_response(ErrorResponse::Alert);
+    }
+    if form.duration_months < 1 {
+        return Err("Invalid duration").map_err_response(ErrorResponse::Alert);
+    }
+
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    let before = sqlx::query!(
+        r#"SELECT member_id, plan_id, to_jsonb(payments) AS "payment!" FROM payments WHERE id = $1 FOR UPDATE"#,
+        payment_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    if let Some(plan_id) = form.plan_id {
+        crate::db::plans::check_duration(
+            plan_id,
+            form.duration_months,
+            before.plan_id,
+            &mut *transaction,
+        )
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+    }
+
+    let after = sqlx::query_scalar!(
+        r#"UPDATE payments
+            SET
+                payment_method = $2,
+                amount_paid = $3,
+                effective_on = $4,
+                duration_months = $5,
+                notes = $6,
+                plan_id = $7
+            WHERE id = $1
+            RETURNING to_jsonb(payments) AS "payment!""#,
+        payment_id,
+        form.payment_method
+            .as_deref()
+            .map(str::trim)
+            .filter(|method| !method.is_empty()),
+        form.amount_paid,
+        form.effective_on,
+        form.duration_months,
+        form.notes.as_deref().filter(|notes| !notes.is_empty()),
+        form.plan_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(before.member_id),
+        action: "payment.edit",
+        reason: Some(form.reason.trim()),
+        before: Some(before.payment),
+        after: Some(after),
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(updated_row(
+        nest.as_str(),
+        payment_id,
+        &state,
+        "Payment Updated Successfully",
+    )
+    .await)
+}
+
+pub async fn void_form(
+    nest: NestedPath,
+    Path(payment_id): Path<i32>,
+    State(state): State<crate::AppState>,
+) -> Result<Markup, Response> {
+    let payment = crate::db::payments::by_id(payment_id, &state)
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(html! {
+        h1 ."font-bold"."text-xl" {(if payment.voided {"Restore"} else {"Void"})" Payment #"(payment.id)}
+        (payment_header(&payment))
+        @if let Some(void_reason) = &payment.void_reason {
+            p {"Voided: "(void_reason)}
+        }
+        ."form-response" {}
+        ."divider" {}
+        form ."mt-3" hx-post={(nest.as_str())"/void/"(payment.id)} hx-target="previous .form-response" hx-indicator="#modal-loading" {
+            ."form-control"."w-full" {
+                ."label" { span ."label-text" {"Reason"} }
+                input type="text" name="reason" required ."input"."input-bordered"."w-full";
+                ."alert"."alert-warning"."mt-4"."w-full" role="warning" {
+                    (icons::warning())
+                    span {"Voided payments stay on record but no longer count towards membership. Deleting a payment removes it entirely and cannot be undone."}
+                }
+                ."form-control"."mt-4"."flex-row"."justify-center"."gap-2" {
+                    @if payment.voided {
+                        button name="action" value="restore" ."btn"."btn-outline"."btn-primary" {"RESTORE"}
+                    } @else {
+                        button name="action" value="void" ."btn"."btn-outline"."btn-primary" {(icons::warning())" VOID"}
+                    }
+                    button name="action" value="delete" ."btn"."btn-outline"."btn-error" {(icons::warning())" DELETE"}
+                }
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoidAction {
+    Void,
+    Restore,
+    Delete,
+}
+
+#[derive(Deserialize)]
+pub struct VoidPaymentFormData {
+    action: VoidAction,
+    reason: String,
+}
+
+pub async fn void_payment(
+    nest: NestedPath,
+    Path(payment_id): Path<i32>,
+    State(state): State<crate::AppState>,
+    Extension(admin): Extension<crate::auth::Jwt>,
+    Form(form): Form<VoidPaymentFormData>,
+) -> Result<Markup, Response> {
+    let reason = form.reason.trim();
+    if reason.is_empty() {
+        return Err("A reason is required").map_err_response(ErrorResponse::Alert);
+    }
+
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    let before = sqlx::query!(
+        r#"SELECT member_id, to_jsonb(payments) AS "payment!" FROM payments WHERE id = $1 FOR UPDATE"#,
+        payment_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    let (action, after) = match form.action {
+        VoidAction::Delete => {
+            sqlx::query!("DELETE FROM payments WHERE id = $1", payment_id)
+                .execute(&mut *transaction)
+                .await
+                .map_err_response(ErrorResponse::Alert)?;
+            ("payment.delete", None)
+        }
+        VoidAction::Void | VoidAction::Restore => {
+            let voided = matches!(form.action, VoidAction::Void);
+            let after = sqlx::query_scalar!(
+                r#"UPDATE payments
+                    SET voided = $2, void_reason = $3
+                    WHERE id = $1
+                    RETURNING to_jsonb(payments) AS "payment!""#,
+                payment_id,
+                voided,
+                Some(reason).filter(|_| voided)
+            )
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err_response(ErrorResponse::Alert)?;
+            (
+                if voided {
+                    "payment.void"
+                } else {
+                    "payment.restore"
+                },
+                Some(after),
+            )
+        }
+    };
+
+    AuditEvent {
+        account_id: Some(admin.account.id),
+        member_id: Some(before.member_id),
+        action,
+        reason: Some(reason),
+        before: Some(before.payment),
+        after,
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::Alert)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::Alert)?;
+
+    Ok(match form.action {
+        VoidAction::Delete => html! {
+            template { tr #{"payment_row_"(payment_id)} hx-swap-oob="delete" {} }
+            script {(PreEscaped("$('#modal')[0].close();"))}
+            (components::ToastAlert::Success("Payment Deleted Successfully"))
+        },
+        VoidAction::Void => {
+            updated_row(
+                nest.as_str(),
+                payment_id,
+                &state,
+                "Payment Voided Successfully",
+            )
+            .await
+        }
+        VoidAction::Restore => {
+            updated_row(
+                nest.as_str(),
+                payment_id,
+                &state,
+                "Payment Restored Successfully",
+            )
+            .await
+        }
+    })
+}
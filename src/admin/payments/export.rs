@@ -0,0 +1,56 @@
+use axum::{extract::State, response::Response};
+use axum_extra::extract::Query;
+use time::OffsetDateTime;
+
+use crate::{
+    admin::csv_export::{csv_response, optional},
+    db::payments::{PaymentRow, PaymentsQuery},
+};
+
+const HEADER: &[&str] = &[
+    "id",
+    "member_id",
+    "first_name",
+    "last_name",
+    "email",
+    "effective_on",
+    "duration_months",
+    "amount_paid",
+    "payment_method",
+    "transaction_id",
+    "notes",
+    "created_on",
+    "voided",
+    "void_reason",
+];
+
+fn payment_record(payment: PaymentRow) -> Vec<String> {
+    vec![
+        payment.id.to_string(),
+        payment.member_id.to_string(),
+        payment.first_name,
+        payment.last_name,
+        payment.email,
+        payment.effective_on.to_string(),
+        payment.duration_months.to_string(),
+        payment.amount_paid.round_dp(2).to_string(),
+        payment.payment_method.unwrap_or_default(),
+        optional(payment.transaction_id),
+        payment.notes.unwrap_or_default(),
+        payment.created_on.to_string(),
+        payment.voided.to_string(),
+        payment.void_reason.unwrap_or_default(),
+    ]
+}
+
+pub async fn payments_csv(
+    Query(params): Query<PaymentsQuery>,
+    State(state): State<crate::AppState>,
+) -> Response {
+    csv_response(
+        &format!("payments-{}.csv", OffsetDateTime::now_utc().date()),
+        HEADER,
+        crate::db::payments::export(params, state),
+        payment_record,
+    )
+}
@@ -1,10 +1,20 @@
 use axum::{routing::get, Router};
 
+mod edit_payment;
+pub mod export;
 mod search;
 
 pub fn router(state: crate::AppState) -> Router {
     Router::new()
         .route("/", get(search::search_form))
         .route("/search", get(search::search_results))
+        .route(
+            "/edit/{payment_id}",
+            get(edit_payment::edit_form).post(edit_payment::edit_payment),
+        )
+        .route(
+            "/void/{payment_id}",
+            get(edit_payment::void_form).post(edit_payment::void_payment),
+        )
         .with_state(state.clone())
 }
@@ -1,18 +1,30 @@
 use axum::{
-    extract::{NestedPath, Query, State},
+    extract::{NestedPath, State},
     http::HeaderMap,
     response::Response,
 };
+use axum_extra::extract::Query;
 use maud::{html, Markup};
 use tokio::try_join;
 
 use crate::{
-    db::payments::PaymentsQuery,
+    db::payments::{PaymentRow, PaymentsQuery},
     err_responses::{ErrorResponse, MapErrorResponse},
     icons,
 };
 
-pub async fn search_form(nest: NestedPath, Query(params): Query<PaymentsQuery>) -> Markup {
+pub async fn search_form(
+    nest: NestedPath,
+    Query(params): Query<PaymentsQuery>,
+    State(state): State<crate::AppState>,
+) -> Markup {
+    let payment_methods = sqlx::query_scalar!(
+        r#"SELECT DISTINCT payment_method AS "payment_method!" FROM payments WHERE payment_method IS NOT NULL ORDER BY payment_method"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .unwrap_or_default();
+
     html! { #"payments_list" ."w-full"."max-w-4xl"."mx-auto" {
         #"payments_search" ."card"."bg-base-200"."w-full"."border"."border-secondary" {
             form hx-get={(nest.as_str())"/search"} hx-target="#payments_search_results" hx-push-url="true" ."card-body" {
@@ -21,13 +33,74 @@ pub async fn search_form(nest: NestedPath, Query(params): Query<PaymentsQuery>)
                     input type="text" name="member_search" placeholder="Search by Member" value=[&params.member_search] ."grow"."bg-inherit";
                     span ."text-secondary" {(icons::search())}
                 }
+                ."divider" {"Filter Results"}
+                ."grid"."grid-cols-1"."md:grid-cols-2"."gap-x-6" {
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Effective From"}
+                            input type="date" name="effective_from" value=[params.effective_from] ."input"."input-bordered";
+                        }
+                    }
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Effective To"}
+                            input type="date" name="effective_to" value=[params.effective_to] ."input"."input-bordered";
+                        }
+                    }
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Recorded From"}
+                            input type="date" name="created_from" value=[params.created_from] ."input"."input-bordered";
+                        }
+                    }
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Recorded To"}
+                            input type="date" name="created_to" value=[params.created_to] ."input"."input-bordered";
+                        }
+                    }
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Minimum Amount"}
+                            input type="number" name="min_amount" min="0" step="0.01" value=[params.min_amount] ."input"."input-bordered";
+                        }
+                    }
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Maximum Amount"}
+                            input type="number" name="max_amount" min="0" step="0.01" value=[params.max_amount] ."input"."input-bordered";
+                        }
+                    }
+                    ."form-control" {
+                        label ."label"."cursor-pointer" {
+                            span ."label-text" {"Duration (Months)"}
+                            input type="number" name="duration_months" min="0" step="1" value=[params.duration_months] ."input"."input-bordered";
+                        }
+                    }
+                }
+                ."form-control" {
+                    ."label" { span ."label-text" {"Payment Method"} }
+                    ."flex"."flex-wrap"."gap-x-6" {
+                        @for method in payment_methods {
+                            label ."label"."cursor-pointer"."gap-2" {
+                                input type="checkbox" name="payment_method" value=(method) checked[params.payment_method.contains(&method)] ."checkbox"."checkbox-primary";
+                                span ."label-text" {(method)}
+                            }
+                        }
+                    }
+                }
                 ."divider" {"Sort Results"}
                 ."form-control" {
                     label ."label"."cursor-pointer" {
                         span ."label-text" {"Sort By"}
                         select name="sort_by" ."select"."select-bordered" {
                             option value="effective_on" selected[params.sort_by=="effective_on"] {"Effective Date"}
+                            option value="member_name" selected[params.sort_by=="member_name"] {"Member Name"}
+                            option value="email" selected[params.sort_by=="email"] {"Member Email"}
                             option value="amount_paid" selected[params.sort_by=="amount_paid"] {"Amount Paid"}
+                            option value="payment_method" selected[params.sort_by=="payment_method"] {"Payment Method"}
+                            option value="duration_months" selected[params.sort_by=="duration_months"] {"Duration"}
+                            option value="created_on" selected[params.sort_by=="created_on"] {"Recorded Date"}
                         }
                     }
                 }
@@ -47,6 +120,37 @@ pub async fn search_form(nest: NestedPath, Query(params): Query<PaymentsQuery>)
     } }
 }
 
+pub fn payment_row(nest: &str, payment: &PaymentRow, oob: bool) -> Markup {
+    html! {
+        tr #{"payment_row_"(payment.id)} ."opacity-60"[payment.voided] hx-swap-oob=[oob.then_some("true")] {
+            td { a href={"/admin/members?search="(payment.email)} target="_blank" ."btn"."btn-link" {(payment.last_name)", "(payment.first_name)} }
+            td { a href={"mailto:"(payment.email)} ."btn"."btn-link" {(payment.email)} }
+            td {(payment.effective_on)}
+            td {
+                "$"(payment.amount_paid.round_dp(2))
+                @if payment.voided {
+                    " " ."badge"."badge-error"."badge-outline" title=[&payment.void_reason] {"Voided"}
+                }
+            }
+            td {(payment.duration_months)" mo."}
+            td {(payment.payment_method.as_deref().unwrap_or_default())}
+            td {
+                @match payment.payment_method.as_deref() {
+                    Some("webconnex") => { a href={"/.webconnex/redirect/transaction/"(payment.transaction_id.unwrap_or_default())} target="_blank" ."btn"."btn-circle"."btn-outline" {(icons::open_external())} },
+                    Some("donorbox") => { a href={"https://donorbox.org/org_admin/donations/"(payment.transaction_id.unwrap_or_default())} target="_blank" ."btn"."btn-circle"."btn-outline" {(icons::open_external())} },
+                    _ => {}
+                }
+            }
+            td ."*:mr-1" {
+                button ."btn"."btn-sm"."btn-outline"."btn-secondary" onclick="openModal()" hx-get={(nest)"/edit/"(payment.id)} hx-target="#modal-content" {"Edit"}
+                button ."btn"."btn-sm"."btn-outline"."btn-secondary" onclick="openModal()" hx-get={(nest)"/void/"(payment.id)} hx-target="#modal-content" {
+                    (if payment.voided {"Restore"} else {"Void"})
+                }
+            }
+        }
+    }
+}
+
 pub struct PaginationRequest {
     count: u64,
     offset: u64,
@@ -59,7 +163,7 @@ pub async fn search_results(
     State(state): State<crate::AppState>,
 ) -> Result<Markup, Response> {
     if headers.contains_key("X-Rebuild-Page") {
-        return Ok(search_form(nest, Query(params)).await);
+        return Ok(search_form(nest, Query(params), State(state)).await);
     }
 
     let (payments, total) = try_join!(
@@ -101,24 +205,13 @@ pub async fn search_results(
                 th {"Member Email"}
                 th {"Effective On"}
                 th {"Amount Paid"}
+                th {"Duration"}
                 th {"Payment Method"}
                 th {"Open"}
+                th {"Actions"}
             }}
             @for payment in &payments {
-                tr {
-                    td { a href={"/admin/members?search="(payment.email)} target="_blank" ."btn"."btn-link" {(payment.last_name)", "(payment.first_name)} }
-                    td { a href={"mailto:"(payment.email)} ."btn"."btn-link" {(payment.email)} }
-                    td {(payment.effective_on)}
-                    td {"$"(payment.amount_paid.round_dp(2))}
-                    td {(payment.payment_method.as_deref().unwrap_or_default())}
-                    td {
-                        @match payment.payment_method.as_deref() {
-                            Some("webconnex") => { a href={"/.webconnex/redirect/transaction/"(payment.transaction_id.unwrap_or_default())} target="_blank" ."btn"."btn-circle"."btn-outline" {(icons::open_external())} },
-                            Some("donorbox") => { a href={"https://donorbox.org/org_admin/donations/"(payment.transaction_id.unwrap_or_default())} target="_blank" ."btn"."btn-circle"."btn-outline" {(icons::open_external())} },
-                            _ => {}
-                        }
-                    }
-                }
+                (payment_row(nest.as_str(), payment, false))
             }
         }}
         ."divider" {}
@@ -129,5 +222,7 @@ pub async fn search_results(
             }
             (pagebtn(next, "Next"))
         }
+        a href={(nest.as_str())"/export.csv?"(serde_html_form::to_string(&params).unwrap_or_default())} download
+            ."btn"."btn-outline"."btn-secondary"."btn-sm"."block"."w-fit"."mx-auto"."mt-3" {(icons::download())" Export CSV"}
     })
 }
@@ -1,5 +1,6 @@
 use axum::response::IntoResponse;
 use maud::{html, Markup, PreEscaped, Render, DOCTYPE};
+use time::{macros::format_description, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::icons;
@@ -39,6 +40,14 @@ pub fn layout(navbar_options: Markup, main_content: Option<Markup>) -> Markup {
     }
 }
 
+pub fn format_timestamp(timestamp: &OffsetDateTime) -> String {
+    timestamp
+        .format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute] UTC"
+        ))
+        .unwrap_or_default()
+}
+
 pub enum ToastAlert<'a> {
     Success(&'a str),
     Error(&'a str),
@@ -0,0 +1,250 @@
+use sea_query::{
+    extension::postgres::PgExpr, Alias, Asterisk, Expr, Iden, Order, PostgresQueryBuilder, Query,
+    SimpleExpr,
+};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use sqlx::FromRow;
+use time::{Date, OffsetDateTime};
+
+use super::members::Members;
+
+#[derive(Default)]
+pub struct AuditEvent<'a> {
+    pub account_id: Option<i32>,
+    pub member_id: Option<i32>,
+    pub action: &'a str,
+    pub reason: Option<&'a str>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl AuditEvent<'_> {
+    pub async fn record<'e>(&self, db: impl sqlx::PgExecutor<'e>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"INSERT INTO audit_events (account_id, member_id, action, reason, before, after)
+                VALUES                  ($1,         $2,        $3,     $4,     $5,     $6)"#,
+            self.account_id,
+            self.member_id,
+            self.action,
+            self.reason,
+            self.before,
+            self.after
+        )
+        .execute(db)
+        .await
+        .map(|_| ())
+    }
+}
+
+pub async fn member_snapshot<'e>(
+    member_id: i32,
+    db: impl sqlx::PgExecutor<'e>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT to_jsonb(members) AS "snapshot!" FROM members WHERE id = $1"#,
+        member_id
+    )
+    .fetch_one(db)
+    .await
+}
+
+#[serde_inline_default]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AuditEventsQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<Date>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Date>,
+
+    #[serde_inline_default(25)]
+    pub count: u64,
+    #[serde_inline_default(0)]
+    pub offset: u64,
+}
+
+#[derive(FromRow)]
+#[allow(dead_code)]
+pub struct AuditEventRow {
+    pub id: i32,
+    pub account_id: Option<i32>,
+    pub member_id: Option<i32>,
+    pub action: String,
+    pub reason: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub created_at: OffsetDateTime,
+    pub account_email: Option<String>,
+    pub member_name: Option<String>,
+    pub member_email: Option<String>,
+}
+
+trait AuditEventsQueryFilter {
+    fn with_audit_event_joins(&mut self) -> &mut Self;
+    fn audit_events_query_filter(&mut self, params: &AuditEventsQuery) -> &mut Self;
+}
+
+impl AuditEventsQueryFilter for sea_query::SelectStatement {
+    fn with_audit_event_joins(&mut self) -> &mut Self {
+        self.from(AuditEvents::Table)
+            .left_join(
+                Accounts::Table,
+                Expr::col((AuditEvents::Table, AuditEvents::AccountId))
+                    .equals((Accounts::Table, Accounts::Id)),
+            )
+            .left_join(
+                Members::Table,
+                Expr::col((AuditEvents::Table, AuditEvents::MemberId))
+                    .equals((Members::Table, Members::Id)),
+            )
+    }
+
+    fn audit_events_query_filter(&mut self, params: &AuditEventsQuery) -> &mut Self {
+        self.conditions(
+            params.action.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((AuditEvents::Table, AuditEvents::Action))
+                        .eq(params.action.as_deref().unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.account_id.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((AuditEvents::Table, AuditEvents::AccountId))
+                        .eq(params.account_id.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.member_id.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((AuditEvents::Table, AuditEvents::MemberId))
+                        .eq(params.member_id.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.member_search.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Members::Table, Members::FirstName))
+                        .concat(SimpleExpr::Constant(" ".into()))
+                        .concat(Expr::col((Members::Table, Members::LastName)))
+                        .ilike(format!("%{}%", params.member_search.as_ref().unwrap()))
+                        .or(Expr::col((Members::Table, Members::Email))
+                            .ilike(format!("%{}%", params.member_search.as_ref().unwrap()))),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.from.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((AuditEvents::Table, AuditEvents::CreatedAt))
+                        .gte(params.from.unwrap().midnight().assume_utc()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.to.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((AuditEvents::Table, AuditEvents::CreatedAt)).lt(params
+                        .to
+                        .unwrap()
+                        .next_day()
+                        .unwrap_or(Date::MAX)
+                        .midnight()
+                        .assume_utc()),
+                );
+            },
+            |_| {},
+        )
+    }
+}
+
+pub async fn search(
+    params: &AuditEventsQuery,
+    state: &crate::AppState,
+) -> Result<Vec<AuditEventRow>, sqlx::Error> {
+    let (query, values) = Query::select()
+        .column((AuditEvents::Table, Asterisk))
+        .expr_as(
+            Expr::col((Accounts::Table, Accounts::Email)),
+            Alias::new("account_email"),
+        )
+        .expr_as(
+            Expr::col((Members::Table, Members::LastName))
+                .concat(SimpleExpr::Constant(", ".into()))
+                .concat(Expr::col((Members::Table, Members::FirstName))),
+            Alias::new("member_name"),
+        )
+        .expr_as(
+            Expr::col((Members::Table, Members::Email)),
+            Alias::new("member_email"),
+        )
+        .with_audit_event_joins()
+        .audit_events_query_filter(params)
+        .order_by((AuditEvents::Table, AuditEvents::CreatedAt), Order::Desc)
+        .order_by((AuditEvents::Table, AuditEvents::Id), Order::Desc)
+        .limit(params.count)
+        .offset(params.offset)
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_as_with::<_, AuditEventRow, _>(&query, values)
+        .fetch_all(&state.db_pool)
+        .await
+}
+
+pub async fn count(params: &AuditEventsQuery, state: &crate::AppState) -> Result<u64, sqlx::Error> {
+    let (query, values) = Query::select()
+        .expr(Expr::col(Asterisk).count())
+        .with_audit_event_joins()
+        .audit_events_query_filter(params)
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_scalar_with::<_, i64, _>(&query, values)
+        .fetch_one(&state.db_pool)
+        .await
+        .map(|r| r.try_into().unwrap())
+}
+
+#[derive(Iden)]
+#[allow(dead_code)]
+enum AuditEvents {
+    Table,
+    Id,
+    AccountId,
+    MemberId,
+    Action,
+    Reason,
+    Before,
+    After,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+#[allow(dead_code)]
+enum Accounts {
+    Table,
+    Id,
+    Email,
+}
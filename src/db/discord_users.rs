@@ -0,0 +1,53 @@
+use rust_decimal::Decimal;
+use sea_query::Iden;
+use time::OffsetDateTime;
+
+pub struct DiscordUserRow {
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: String,
+    pub joined_at: Option<OffsetDateTime>,
+    pub in_guild: bool,
+    pub synced_at: OffsetDateTime,
+    pub top_role_name: Option<String>,
+    pub top_role_colour: Option<i32>,
+}
+
+pub async fn by_id(
+    discord: Decimal,
+    db_pool: &sqlx::PgPool,
+) -> Result<Option<DiscordUserRow>, sqlx::Error> {
+    sqlx::query_as!(
+        DiscordUserRow,
+        r#"SELECT
+                discord_users.username,
+                discord_users.display_name,
+                discord_users.avatar_url,
+                discord_users.joined_at,
+                discord_users.in_guild,
+                discord_users.synced_at,
+                top_role.name AS "top_role_name?",
+                top_role.colour AS "top_role_colour?"
+            FROM discord_users
+                LEFT JOIN LATERAL (
+                    SELECT name, colour
+                    FROM discord_roles
+                    WHERE id = ANY(discord_users.roles)
+                    ORDER BY position DESC
+                    LIMIT 1
+                ) top_role ON TRUE
+            WHERE discord_users.id = $1"#,
+        discord
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+#[allow(dead_code)]
+#[derive(Iden)]
+pub enum DiscordUsers {
+    Table,
+    Id,
+    Username,
+    DisplayName,
+}
@@ -1,9 +1,11 @@
 use std::str::FromStr;
 
+use async_stream::try_stream;
+use futures::{Stream, TryStreamExt};
 use rust_decimal::Decimal;
 use sea_query::{
     extension::postgres::PgExpr, Asterisk, Expr, Iden, IntoColumnRef, Order, PostgresQueryBuilder,
-    Query, SimpleExpr,
+    Query, SelectStatement, SimpleExpr,
 };
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Deserializer, Serialize};
@@ -11,6 +13,11 @@ use serde_inline_default::serde_inline_default;
 use sqlx::prelude::FromRow;
 use time::Date;
 
+use super::{
+    audit_events::{member_snapshot, AuditEvent},
+    discord_users::DiscordUsers,
+};
+
 fn none_or_empty(val: &Option<String>) -> bool {
     match val.as_deref() {
         None => true,
@@ -56,6 +63,9 @@ pub struct MembersQuery {
     #[serde_inline_default(-1)]
     pub generation_id: i32,
 
+    #[serde_inline_default(-1)]
+    pub plan_id: i32,
+
     #[serde_inline_default(String::from(""))]
     pub sort_by: String,
 
@@ -70,7 +80,6 @@ pub struct MemberDetailsRow {
     pub email: String,
     pub first_name: String,
     pub last_name: String,
-    pub notes: String,
     pub created_on: Date,
     pub discord: Option<Decimal>,
     pub cancelled: bool,
@@ -81,6 +90,8 @@ pub struct MemberDetailsRow {
     pub generation_name: Option<String>,
     pub generation_id: Option<i32>,
     pub is_active: Option<bool>,
+    pub current_plan_id: Option<i32>,
+    pub current_plan_name: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -90,7 +101,6 @@ pub struct MemberRow {
     pub email: String,
     pub first_name: String,
     pub last_name: String,
-    pub notes: String,
     pub created_on: Date,
     pub discord: Option<Decimal>,
     pub cancelled: bool,
@@ -100,12 +110,7 @@ pub struct MemberRow {
 trait MembersQueryFilter {
     fn from_member_details(&mut self) -> &mut Self;
     fn members_query_filter(&mut self, params: &MembersQuery) -> &mut Self;
-    async fn members_discord_filter(
-        &mut self,
-        discord: &Option<String>,
-        http: &serenity::http::Http,
-        guildid: &serenity::model::id::GuildId,
-    ) -> &mut Self;
+    fn members_discord_filter(&mut self, discord: &Option<String>) -> &mut Self;
 }
 
 impl MembersQueryFilter for sea_query::SelectStatement {
@@ -163,33 +168,35 @@ impl MembersQueryFilter for sea_query::SelectStatement {
             },
             |_| {},
         )
+        .conditions(
+            params.plan_id >= 0,
+            |q| {
+                q.and_where(
+                    Expr::col(MemberDetails::CurrentPlanId).eq(Expr::value(params.plan_id)),
+                );
+            },
+            |_| {},
+        )
     }
 
-    async fn members_discord_filter(
-        &mut self,
-        discord: &Option<String>,
-        http: &serenity::http::Http,
-        guildid: &serenity::model::id::GuildId,
-    ) -> &mut Self {
-        let userids = match discord.as_deref() {
-            Some("") => None,
-            Some(val) => http
-                .search_guild_members(*guildid, val, Some(1000))
-                .await
-                .ok(),
-            None => None,
-        }
-        .map(|v| {
-            v.iter()
-                .map(|m| Decimal::from(m.user.id.get()))
-                .collect::<Vec<_>>()
-        });
-
+    fn members_discord_filter(&mut self, discord: &Option<String>) -> &mut Self {
         self.conditions(
-            userids.is_some(),
+            !discord.as_deref().unwrap_or_default().is_empty(),
             |q| {
-                let username_matches = Expr::col(Members::Discord).is_in(userids.unwrap());
-                q.and_where(match Decimal::from_str(discord.as_deref().unwrap()) {
+                let discord = discord.as_deref().unwrap().trim();
+                let username_matches = Expr::col(Members::Discord).in_subquery(
+                    Query::select()
+                        .column(DiscordUsers::Id)
+                        .from(DiscordUsers::Table)
+                        .cond_where(
+                            Expr::col(DiscordUsers::Username)
+                                .ilike(format!("%{}%", discord))
+                                .or(Expr::col(DiscordUsers::DisplayName)
+                                    .ilike(format!("%{}%", discord))),
+                        )
+                        .to_owned(),
+                );
+                q.and_where(match Decimal::from_str(discord) {
                     Ok(userid) => username_matches.or(Expr::col(Members::Discord).eq(userid)),
                     Err(_) => username_matches,
                 });
@@ -199,24 +206,18 @@ impl MembersQueryFilter for sea_query::SelectStatement {
     }
 }
 
-pub async fn search(
-    params: &MembersQuery,
-    state: &crate::AppState,
-) -> Result<Vec<MemberRow>, sqlx::Error> {
+fn select_members(params: &MembersQuery) -> SelectStatement {
     let sort_order = if params.sort_desc {
         Order::Desc
     } else {
         Order::Asc
     };
 
-    let (query, values) = Query::select()
+    Query::select()
         .column(Asterisk)
         .from_member_details()
         .members_query_filter(params)
-        .members_discord_filter(&params.discord, &state.discord_http, &state.discord_guild)
-        .await
-        .limit(params.count)
-        .offset(params.offset)
+        .members_discord_filter(&params.discord)
         .order_by_columns(match params.sort_by.as_str() {
             "firstname" => vec![
                 (Members::FirstName.into_column_ref(), sort_order.clone()),
@@ -235,6 +236,16 @@ pub async fn search(
                 sort_order.clone(),
             )],
         })
+        .to_owned()
+}
+
+pub async fn search(
+    params: &MembersQuery,
+    state: &crate::AppState,
+) -> Result<Vec<MemberRow>, sqlx::Error> {
+    let (query, values) = select_members(params)
+        .limit(params.count)
+        .offset(params.offset)
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_as_with::<_, MemberRow, _>(&query, values)
@@ -242,13 +253,81 @@ pub async fn search(
         .await
 }
 
+pub fn export(
+    params: MembersQuery,
+    state: crate::AppState,
+) -> impl Stream<Item = Result<MemberDetailsRow, sqlx::Error>> {
+    try_stream! {
+        let (query, values) = select_members(&params)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let mut rows =
+            sqlx::query_as_with::<_, MemberDetailsRow, _>(&query, values).fetch(&state.db_pool);
+        while let Some(row) = rows.try_next().await? {
+            yield row;
+        }
+    }
+}
+
+pub async fn recipients(
+    params: &MembersQuery,
+    db_pool: &sqlx::PgPool,
+) -> Result<Vec<MemberRow>, sqlx::Error> {
+    let (query, values) = select_members(params)
+        .and_where(Expr::col((Members::Table, Members::Banned)).eq(false))
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_as_with::<_, MemberRow, _>(&query, values)
+        .fetch_all(db_pool)
+        .await
+}
+
+pub async fn update_status(
+    member_id: i32,
+    account_id: i32,
+    action: &str,
+    reason: &str,
+    cancelled: Option<bool>,
+    banned: Option<bool>,
+    db_pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    let before = member_snapshot(member_id, &mut *transaction).await?;
+    let after = sqlx::query_scalar!(
+        r#" UPDATE members
+            SET
+                cancelled = COALESCE($2, cancelled),
+                banned = COALESCE($3, banned)
+            WHERE id = $1
+            RETURNING to_jsonb(members) AS "after!""#,
+        member_id,
+        cancelled,
+        banned
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    AuditEvent {
+        account_id: Some(account_id),
+        member_id: Some(member_id),
+        action,
+        reason: Some(reason.trim()).filter(|reason| !reason.is_empty()),
+        before: Some(before),
+        after: Some(after),
+    }
+    .record(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
 pub async fn count(params: &MembersQuery, state: &crate::AppState) -> Result<u64, sqlx::Error> {
     let (query, values) = Query::select()
         .expr(Expr::col(Asterisk).count())
         .from_member_details()
         .members_query_filter(params)
-        .members_discord_filter(&params.discord, &state.discord_http, &state.discord_guild)
-        .await
+        .members_discord_filter(&params.discord)
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_scalar_with::<_, i64, _>(&query, values)
@@ -268,6 +347,7 @@ pub enum Members {
     ReasonRemoved,
     CreatedOn,
     Discord,
+    Banned,
 }
 
 #[allow(dead_code)]
@@ -281,4 +361,6 @@ enum MemberDetails {
     ConsecutiveUntil,
     IsActive,
     FirstPayment,
+    CurrentPlanId,
+    CurrentPlanName,
 }
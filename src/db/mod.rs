@@ -1,2 +1,5 @@
+pub mod audit_events;
+pub mod discord_users;
 pub mod members;
 pub mod payments;
+pub mod plans;
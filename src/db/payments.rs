@@ -1,9 +1,11 @@
 use std::str::FromStr;
 
+use async_stream::try_stream;
+use futures::{Stream, TryStreamExt};
 use rust_decimal::Decimal;
 use sea_query::{
-    extension::postgres::PgExpr, Asterisk, Expr, Iden, Order, PostgresQueryBuilder, Query,
-    SimpleExpr,
+    extension::postgres::PgExpr, Asterisk, Expr, Iden, IntoColumnRef, Order, PostgresQueryBuilder,
+    Query, SelectStatement, SimpleExpr,
 };
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
@@ -11,7 +13,19 @@ use serde_inline_default::serde_inline_default;
 use sqlx::FromRow;
 use time::Date;
 
-use super::members::Members;
+use super::{audit_events::AuditEvent, members::Members};
+
+pub const PAYMENT_METHODS: &[(&str, &str)] = &[
+    ("cash", "Cash"),
+    ("card", "Card"),
+    ("volunteer", "Volunteering"),
+    ("grace-period", "Grace Period"),
+    ("ethics-committee", "Ethics Committee"),
+    ("exec-board", "Executive Board"),
+    ("other", "Other"),
+];
+
+pub const WEBHOOK_PAYMENT_METHODS: &[&str] = &["webconnex", "donorbox", "givingfuel"];
 
 #[serde_inline_default]
 #[derive(Deserialize, Serialize, Clone)]
@@ -19,6 +33,23 @@ pub struct PaymentsQuery {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub member_search: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_from: Option<Date>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_to: Option<Date>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_from: Option<Date>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_to: Option<Date>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub payment_method: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_amount: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_months: Option<i32>,
+
     #[serde_inline_default(12)]
     pub count: u64,
     #[serde_inline_default(0)]
@@ -41,6 +72,9 @@ pub struct PaymentRow {
     pub payment_method: Option<String>,
     pub transaction_id: Option<i32>,
     pub notes: Option<String>,
+    pub voided: bool,
+    pub void_reason: Option<String>,
+    pub plan_id: Option<i32>,
     pub first_name: String,
     pub last_name: String,
     pub email: String,
@@ -77,36 +111,142 @@ impl PaymentsQueryFilter for sea_query::SelectStatement {
             },
             |_| {},
         )
+        .conditions(
+            params.effective_from.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::EffectiveOn))
+                        .gte(params.effective_from.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.effective_to.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::EffectiveOn))
+                        .lte(params.effective_to.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.created_from.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::CreatedOn))
+                        .gte(params.created_from.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.created_to.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::CreatedOn))
+                        .lte(params.created_to.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            !params.payment_method.is_empty(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::PaymentMethod))
+                        .is_in(params.payment_method.clone()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.min_amount.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::AmountPaid))
+                        .gte(params.min_amount.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.max_amount.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::AmountPaid))
+                        .lte(params.max_amount.unwrap()),
+                );
+            },
+            |_| {},
+        )
+        .conditions(
+            params.duration_months.is_some(),
+            |q| {
+                q.and_where(
+                    Expr::col((Payments::Table, Payments::DurationMonths))
+                        .eq(params.duration_months.unwrap()),
+                );
+            },
+            |_| {},
+        )
     }
 }
 
-pub async fn search(
-    params: &PaymentsQuery,
-    state: &crate::AppState,
-) -> Result<Vec<PaymentRow>, sqlx::Error> {
+fn select_payments(params: &PaymentsQuery) -> SelectStatement {
     let sort_order = if params.sort_desc {
         Order::Desc
     } else {
         Order::Asc
     };
 
-    let (query, values) = Query::select()
+    Query::select()
         .column((Payments::Table, Asterisk))
-        .column((Members::Table, Asterisk))
+        .columns([
+            (Members::Table, Members::FirstName),
+            (Members::Table, Members::LastName),
+            (Members::Table, Members::Email),
+        ])
         .from(Payments::Table)
         .inner_join(
             Members::Table,
             Expr::col(Payments::MemberId).equals((Members::Table, Members::Id)),
         )
         .payments_query_filter(params)
-        .order_by(
-            match params.sort_by.as_str() {
-                "effective_on" => Payments::EffectiveOn,
-                "amount_paid" => Payments::AmountPaid,
-                _ => Payments::Id,
-            },
-            sort_order,
-        )
+        .order_by_columns(match params.sort_by.as_str() {
+            "member_name" => vec![
+                (Members::LastName.into_column_ref(), sort_order.clone()),
+                (Members::FirstName.into_column_ref(), sort_order.clone()),
+            ],
+            "email" => vec![(Members::Email.into_column_ref(), sort_order.clone())],
+            "effective_on" => vec![(Payments::EffectiveOn.into_column_ref(), sort_order.clone())],
+            "amount_paid" => vec![(Payments::AmountPaid.into_column_ref(), sort_order.clone())],
+            "payment_method" => vec![(
+                Payments::PaymentMethod.into_column_ref(),
+                sort_order.clone(),
+            )],
+            "duration_months" => vec![(
+                Payments::DurationMonths.into_column_ref(),
+                sort_order.clone(),
+            )],
+            "created_on" => vec![(
+                (Payments::Table, Payments::CreatedOn).into_column_ref(),
+                sort_order.clone(),
+            )],
+            _ => vec![(
+                (Payments::Table, Payments::Id).into_column_ref(),
+                sort_order.clone(),
+            )],
+        })
+        .to_owned()
+}
+
+pub async fn search(
+    params: &PaymentsQuery,
+    state: &crate::AppState,
+) -> Result<Vec<PaymentRow>, sqlx::Error> {
+    let (query, values) = select_payments(params)
         .limit(params.count)
         .offset(params.offset)
         .build_sqlx(PostgresQueryBuilder);
@@ -116,6 +256,81 @@ pub async fn search(
         .await
 }
 
+pub fn export(
+    params: PaymentsQuery,
+    state: crate::AppState,
+) -> impl Stream<Item = Result<PaymentRow, sqlx::Error>> {
+    try_stream! {
+        let (query, values) = select_payments(&params).build_sqlx(PostgresQueryBuilder);
+
+        let mut rows =
+            sqlx::query_as_with::<_, PaymentRow, _>(&query, values).fetch(&state.db_pool);
+        while let Some(row) = rows.try_next().await? {
+            yield row;
+        }
+    }
+}
+
+pub async fn by_id(payment_id: i32, state: &crate::AppState) -> Result<PaymentRow, sqlx::Error> {
+    sqlx::query_as!(
+        PaymentRow,
+        r#"SELECT payments.*, first_name, last_name, email
+            FROM payments
+                INNER JOIN members ON members.id = payments.member_id
+            WHERE payments.id = $1"#,
+        payment_id
+    )
+    .fetch_one(&state.db_pool)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct NewPayment {
+    pub payment_method: String,
+    pub amount_paid: Option<Decimal>,
+    pub transaction_id: Option<i32>,
+    pub effective_on: Date,
+    pub duration_months: i32,
+    pub plan_id: Option<i32>,
+    pub notes: Option<String>,
+}
+
+pub async fn create(
+    member_id: i32,
+    account_id: i32,
+    payment: &NewPayment,
+    db_pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    let after = sqlx::query_scalar!(
+        r#"INSERT INTO payments (member_id, effective_on, duration_months, amount_paid, payment_method, transaction_id, notes, plan_id)
+            VALUES              ($1,        $2,           $3,              $4,          $5,             $6,             $7,    $8)
+            RETURNING to_jsonb(payments) AS "payment!""#,
+        member_id,
+        payment.effective_on,
+        payment.duration_months,
+        payment.amount_paid.unwrap_or(Decimal::ZERO),
+        payment.payment_method,
+        payment.transaction_id,
+        payment.notes,
+        payment.plan_id
+    ).fetch_one(&mut *transaction)
+    .await?;
+
+    AuditEvent {
+        account_id: Some(account_id),
+        member_id: Some(member_id),
+        action: "payment.create",
+        after: Some(after),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
 pub async fn count(params: &PaymentsQuery, state: &crate::AppState) -> Result<u64, sqlx::Error> {
     let (query, values) = Query::select()
         .expr(Expr::col(Asterisk).count())
@@ -146,4 +361,7 @@ enum Payments {
     PaymentMethod,
     TransactionId,
     Notes,
+    Voided,
+    VoidReason,
+    PlanId,
 }
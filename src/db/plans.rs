@@ -0,0 +1,62 @@
+use rust_decimal::Decimal;
+
+pub struct PlanRow {
+    pub id: i32,
+    pub name: String,
+    pub price: Decimal,
+    pub duration_months: i32,
+    pub benefits: String,
+    pub archived: bool,
+}
+
+pub async fn active(db_pool: &sqlx::PgPool) -> Vec<PlanRow> {
+    sqlx::query_as!(
+        PlanRow,
+        "SELECT * FROM plans WHERE NOT archived ORDER BY price, duration_months"
+    )
+    .fetch_all(db_pool)
+    .await
+    .unwrap_or_default()
+}
+
+pub async fn active_or_current(
+    current_plan_id: Option<i32>,
+    db_pool: &sqlx::PgPool,
+) -> Vec<PlanRow> {
+    sqlx::query_as!(
+        PlanRow,
+        "SELECT * FROM plans WHERE NOT archived OR id = $1 ORDER BY price, duration_months",
+        current_plan_id
+    )
+    .fetch_all(db_pool)
+    .await
+    .unwrap_or_default()
+}
+
+pub async fn check_duration(
+    plan_id: i32,
+    duration_months: i32,
+    current_plan_id: Option<i32>,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<(), String> {
+    let plan = sqlx::query!(
+        "SELECT name, duration_months, archived FROM plans WHERE id = $1",
+        plan_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|err| err.to_string())?
+    .ok_or("Plan not found")?;
+
+    if plan.archived && current_plan_id != Some(plan_id) {
+        return Err(format!("The {} plan is archived", plan.name));
+    }
+    if plan.duration_months != duration_months {
+        return Err(format!(
+            "The {} plan lasts {} months, not {}",
+            plan.name, plan.duration_months, duration_months
+        ));
+    }
+
+    Ok(())
+}
@@ -7,11 +7,48 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use reqwest::header;
-use rust_decimal::Decimal;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
 use serenity::{builder::*, model::prelude::*};
+use sha2::{Digest, Sha256};
 use time::macros::date;
 
-use crate::db::members::MemberDetailsRow;
+use crate::{
+    db::{audit_events::AuditEvent, members::MemberDetailsRow},
+    send_email::{queue_email, EmailValues},
+};
+
+pub mod bans;
+pub mod client;
+pub mod invites;
+mod members;
+pub mod mock;
+pub mod notifications;
+pub mod roles;
+pub mod users;
+
+pub async fn insert_discord_setting(
+    id: &str,
+    value: &str,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO discord_settings (id, value) VALUES ($1, $2) ON CONFLICT (id) DO UPDATE SET value = excluded.value",
+        id,
+        value
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn get_discord_setting(
+    id: &str,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!("SELECT value FROM discord_settings WHERE id = $1", id)
+        .fetch_optional(db)
+        .await
+}
 
 async fn whois(
     user_id: UserId,
@@ -88,6 +125,11 @@ async fn whois(
                                     member.is_active.unwrap_or(false).to_string(),
                                     false,
                                 )
+                                .field(
+                                    "plan",
+                                    member.current_plan_name.as_deref().unwrap_or("null"),
+                                    false,
+                                )
                         })
                         .collect(),
                 ),
@@ -95,56 +137,372 @@ async fn whois(
     }
 }
 
-struct RegisterUserResponse {
+async fn membership(
+    user_id: UserId,
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, StatusCode> {
+    let result = sqlx::query_as!(
+        MemberDetailsRow,
+        "SELECT * FROM members NATURAL JOIN member_details WHERE discord=$1",
+        Decimal::from(user_id.get())
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if result.is_empty() {
+        return Ok(CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content("Your Discord account is not registered in MDMA yet. Use the **Accept & Join** button in the server's registration channel with the email you paid with, or ask an admin for help."),
+        ));
+    }
+
+    let mut message = CreateInteractionResponseMessage::new()
+        .ephemeral(true)
+        .add_embeds(
+            result
+                .iter()
+                .map(|member| {
+                    let active = member.is_active.unwrap_or(false);
+                    CreateEmbed::new()
+                        .title(format!("{} {}", member.first_name, member.last_name))
+                        .colour(if active {
+                            Colour::DARK_GREEN
+                        } else {
+                            Colour::RED
+                        })
+                        .field("Status", if active { "Active" } else { "Inactive" }, true)
+                        .field(
+                            "Generation",
+                            member.generation_name.as_deref().unwrap_or("None"),
+                            true,
+                        )
+                        .field(
+                            "Member Since",
+                            member
+                                .consecutive_since
+                                .map(|date| date.to_string())
+                                .unwrap_or_else(|| String::from("N/A")),
+                            false,
+                        )
+                        .field(
+                            "Paid Through",
+                            member
+                                .consecutive_until
+                                .map(|date| date.to_string())
+                                .unwrap_or_else(|| String::from("N/A")),
+                            false,
+                        )
+                })
+                .collect(),
+        );
+    if let Some(renewal_url) = state.secret_store.get("MEMBERSHIP_RENEWAL_URL") {
+        message = message.button(CreateButton::new_link(renewal_url).label("Renew Membership"));
+    }
+    Ok(CreateInteractionResponse::Message(message))
+}
+
+const VERIFICATION_MAX_ATTEMPTS: i32 = 5;
+const VERIFICATION_MAX_CODES_PER_HOUR: i64 = 3;
+
+fn ephemeral_message(content: impl Into<String>) -> CreateInteractionResponse {
+    CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+    )
+}
+
+fn hash_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().as_bytes()))
+}
+
+async fn verification_locked(
+    discord: Decimal,
+    email: &str,
+    executor: impl sqlx::PgExecutor<'_>,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) >= $3 AS "locked!" FROM discord_verification_log
+            WHERE event = 'failed'
+            AND (discord = $1 OR LOWER(email) = LOWER($2))
+            AND created_at > NOW() - INTERVAL '1 hour'"#,
+        discord,
+        email,
+        i64::from(VERIFICATION_MAX_ATTEMPTS)
+    )
+    .fetch_one(executor)
+    .await
+}
+
+async fn log_verification(
+    discord: Decimal,
+    email: &str,
+    event: &str,
+    executor: impl sqlx::PgExecutor<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO discord_verification_log (discord, email, event) VALUES ($1, $2, $3)",
+        discord,
+        email,
+        event
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+struct VerificationMember {
+    id: i32,
     first_name: String,
     last_name: String,
 }
 
-async fn register_user(
+async fn send_verification_code(
     email: &str,
     user_id: UserId,
     assign_role: Option<RoleId>,
-    state: crate::AppState,
-) -> CreateInteractionResponse {
-    match sqlx::query_as!(
-        RegisterUserResponse,
-        "UPDATE members SET discord=$1 WHERE email=LOWER($2) RETURNING first_name, last_name",
-        Decimal::from(user_id.get()),
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, String> {
+    let discord = Decimal::from(user_id.get());
+    let recently_sent = sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM discord_verifications
+            WHERE discord = $1 AND created_at > NOW() - INTERVAL '1 minute'
+        ) AS "exists!""#,
+        discord
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+    if recently_sent {
+        return Ok(ephemeral_message(
+            "A code was sent recently. Please wait a minute before requesting another one.",
+        ));
+    }
+
+    if verification_locked(discord, email, &state.db_pool)
+        .await
+        .map_err(|err| err.to_string())?
+    {
+        return Ok(ephemeral_message(
+            "Too many incorrect codes were entered. Please try again in an hour.",
+        ));
+    }
+
+    let codes_sent = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM discord_verification_log
+            WHERE event = 'sent'
+            AND LOWER(email) = LOWER($1)
+            AND created_at > NOW() - INTERVAL '1 hour'"#,
+        email
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+    if codes_sent >= VERIFICATION_MAX_CODES_PER_HOUR {
+        return Ok(ephemeral_message(
+            "Too many codes were sent to this email. Please try again in an hour.",
+        ));
+    }
+
+    let Some(member) = sqlx::query_as!(
+        VerificationMember,
+        "SELECT id, first_name, last_name FROM members WHERE id = member_id_by_email($1)",
         email
     )
     .fetch_optional(&state.db_pool)
     .await
-    {
-        Ok(Some(result)) => {
-            if let Some(role_id) = assign_role {
-                let _ = state
-                    .discord_http
-                    .add_member_role(state.discord_guild, user_id, role_id, None)
-                    .await;
-            }
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .content(format!(
-                        "Welcome, {} {}! Thank you for joining.",
-                        result.first_name, result.last_name
-                    ))
-                    .ephemeral(true),
+    .map_err(|err| err.to_string())?
+    else {
+        return Ok(ephemeral_message(format!(
+            "Could not find a member with the email {}. Please try again.",
+            email
+        )));
+    };
+
+    let code = format!("{:06}", uuid::Uuid::new_v4().as_u128() % 1_000_000);
+    let mut transaction = state.db_pool.begin().await.map_err(|err| err.to_string())?;
+    sqlx::query!(
+        r#"INSERT INTO discord_verifications (discord, member_id, email, code_hash, assign_role)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (discord) DO UPDATE SET
+                member_id = excluded.member_id,
+                email = excluded.email,
+                code_hash = excluded.code_hash,
+                assign_role = excluded.assign_role,
+                attempts = CASE
+                    WHEN discord_verifications.member_id = excluded.member_id
+                    AND LOWER(discord_verifications.email) = LOWER(excluded.email)
+                    THEN discord_verifications.attempts
+                    ELSE 0
+                END,
+                created_at = NOW(),
+                expires_at = NOW() + INTERVAL '15 minutes'"#,
+        discord,
+        member.id,
+        email,
+        hash_code(&code),
+        assign_role.map(|role_id| Decimal::from(role_id.get()))
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    let values = EmailValues {
+        first_name: member.first_name,
+        last_name: member.last_name,
+        email: email.to_owned(),
+        verification_code: code,
+        ..Default::default()
+    };
+    queue_email(
+        "discord_verification",
+        email,
+        &values,
+        Some(member.id),
+        &mut transaction,
+    )
+    .await?;
+    log_verification(discord, email, "sent", &mut *transaction)
+        .await
+        .map_err(|err| err.to_string())?;
+    transaction.commit().await.map_err(|err| err.to_string())?;
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(format!(
+                "We emailed a verification code to {}. It expires in 15 minutes.",
+                email
+            ))
+            .button(
+                CreateButton::new("mdma_open_verify_modal:_")
+                    .label("Enter Code")
+                    .style(ButtonStyle::Success),
             )
-        }
-        Ok(None) => CreateInteractionResponse::Message(
-            CreateInteractionResponseMessage::new()
-                .content(format!(
-                    "Could not find a member with the email {}. Please try again.",
-                    email
-                ))
-                .ephemeral(true),
-        ),
-        Err(_) => CreateInteractionResponse::Message(
-            CreateInteractionResponseMessage::new()
-                .content("Something went wrong. Please notify an admin and try again later.")
-                .ephemeral(true),
-        ),
+            .ephemeral(true),
+    ))
+}
+
+struct PendingVerification {
+    member_id: i32,
+    email: String,
+    code_hash: String,
+    assign_role: Option<Decimal>,
+    attempts: i32,
+    expired: bool,
+}
+
+struct RegisterUserResponse {
+    id: i32,
+    first_name: String,
+    last_name: String,
+}
+
+async fn verify_code(
+    code: &str,
+    user_id: UserId,
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, sqlx::Error> {
+    let discord = Decimal::from(user_id.get());
+    let mut transaction = state.db_pool.begin().await?;
+
+    let Some(pending) = sqlx::query_as!(
+        PendingVerification,
+        r#"SELECT
+                member_id,
+                email,
+                code_hash,
+                assign_role,
+                attempts,
+                expires_at < NOW() AS "expired!"
+            FROM discord_verifications
+            WHERE discord = $1
+            FOR UPDATE"#,
+        discord
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    else {
+        return Ok(ephemeral_message(
+            "There is no pending verification for your account. Please press Accept & Join to start again.",
+        ));
+    };
+
+    let locked = pending.attempts >= VERIFICATION_MAX_ATTEMPTS
+        || verification_locked(discord, &pending.email, &mut *transaction).await?;
+    if pending.expired || locked {
+        sqlx::query!(
+            "DELETE FROM discord_verifications WHERE discord = $1",
+            discord
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        return Ok(ephemeral_message(if locked {
+            "Too many incorrect codes were entered. Please try again in an hour."
+        } else {
+            "Your verification code has expired. Please press Accept & Join to get a new one."
+        }));
+    }
+
+    if hash_code(code) != pending.code_hash {
+        sqlx::query!(
+            "UPDATE discord_verifications SET attempts = attempts + 1 WHERE discord = $1",
+            discord
+        )
+        .execute(&mut *transaction)
+        .await?;
+        log_verification(discord, &pending.email, "failed", &mut *transaction).await?;
+        transaction.commit().await?;
+        let remaining = VERIFICATION_MAX_ATTEMPTS - pending.attempts - 1;
+        return Ok(ephemeral_message(if remaining > 0 {
+            format!("Incorrect code. You have {} attempt(s) left.", remaining)
+        } else {
+            String::from("Incorrect code. Please try again in an hour.")
+        }));
+    }
+
+    sqlx::query!(
+        "DELETE FROM discord_verifications WHERE discord = $1",
+        discord
+    )
+    .execute(&mut *transaction)
+    .await?;
+    let result = sqlx::query_as!(
+        RegisterUserResponse,
+        "UPDATE members SET discord=$1 WHERE id=$2 RETURNING id, first_name, last_name",
+        discord,
+        pending.member_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    AuditEvent {
+        member_id: Some(result.id),
+        action: "discord.register",
+        after: Some(serde_json::json!({ "discord": user_id.get() })),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    if let Some(role_id) = pending
+        .assign_role
+        .and_then(|role_id| role_id.to_u64())
+        .map(RoleId::new)
+    {
+        let _ = state.discord.add_member_role(user_id, role_id, None).await;
+    }
+    if let Err(err) = roles::assign_registered_roles(result.id, user_id, state).await {
+        tracing::warn!("Assigning roles to member {} failed: {}", result.id, err);
     }
+
+    Ok(ephemeral_message(format!(
+        "Welcome, {} {}! Thank you for joining.",
+        result.first_name, result.last_name
+    )))
 }
 
 fn handle_component_interaction(
@@ -159,6 +517,17 @@ fn handle_component_interaction(
                         .required(true),
                 )]),
         )),
+        Some(("mdma_open_verify_modal", _)) => Ok(CreateInteractionResponse::Modal(
+            CreateModal::new("mdma_verify_modal:_", "Verify Email").components(vec![
+                CreateActionRow::InputText(
+                    CreateInputText::new(InputTextStyle::Short, "CODE", "mdma_verify_code")
+                        .placeholder("123456")
+                        .min_length(6)
+                        .max_length(6)
+                        .required(true),
+                ),
+            ]),
+        )),
         _ => Err(StatusCode::NOT_FOUND),
     }
 }
@@ -167,19 +536,51 @@ async fn handle_modal_interaction(
     event: ModalInteraction,
     state: crate::AppState,
 ) -> Result<CreateInteractionResponse, StatusCode> {
-    match event.data.custom_id.split_once(":") {
-        Some(("mdma_register_modal", role_id)) => match &event.data.components[0].components[0] {
-            ActionRowComponent::InputText(textbox) => Ok(register_user(
-                textbox.value.as_ref().ok_or(StatusCode::BAD_REQUEST)?,
+    let values: std::collections::HashMap<String, String> = event
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .filter_map(|component| match component {
+            ActionRowComponent::InputText(textbox) => Some((
+                textbox.custom_id.clone(),
+                textbox.value.clone().unwrap_or_default(),
+            )),
+            _ => None,
+        })
+        .collect();
+    let value = |custom_id: &str| {
+        values
+            .get(custom_id)
+            .map(|value| value.trim())
+            .ok_or(StatusCode::BAD_REQUEST)
+    };
+
+    let result = match event.data.custom_id.split_once(":") {
+        Some(("mdma_register_modal", role_id)) => {
+            send_verification_code(
+                value("mdma_register_email")?,
                 event.user.id,
                 RoleId::from_str(role_id).ok(),
-                state,
+                &state,
             )
-            .await),
-            &_ => Err(StatusCode::BAD_REQUEST),
-        },
-        _ => Err(StatusCode::NOT_FOUND),
-    }
+            .await
+        }
+        Some(("mdma_verify_modal", _)) => {
+            verify_code(value("mdma_verify_code")?, event.user.id, &state)
+                .await
+                .map_err(|err| err.to_string())
+        }
+        Some(("mdma_payment_modal", member_id)) => {
+            return members::submit_payment(member_id, &values, event.user.id, &state).await
+        }
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+
+    Ok(result.unwrap_or_else(|err| {
+        tracing::error!("Discord registration failed: {}", err);
+        ephemeral_message("Something went wrong. Please notify an admin and try again later.")
+    }))
 }
 
 async fn handle_slash_command(
@@ -221,6 +622,10 @@ async fn handle_slash_command(
             .await
         }
 
+        "membership" => membership(event.user.id, &state).await,
+
+        "member" => members::handle_command(&event, &state).await,
+
         "MDMA WhoIs User" => {
             whois(
                 match event.data.target_id {
@@ -252,8 +657,11 @@ pub async fn handle_request(
         .ok_or(StatusCode::UNAUTHORIZED)?
         .to_str()
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
-    if state
+    let verifier = state
         .discord_verifier
+        .as_ref()
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    if verifier
         .verify(signature, timestamp, body.as_ref())
         .is_err()
     {
@@ -278,77 +686,31 @@ pub async fn handle_request(
         .into_response())
 }
 
-pub async fn create_commands(
-    crate::AppState {
-        discord_http,
-        discord_guild,
-        ..
-    }: &crate::AppState,
-) {
-    CreateCommand::new("register_users")
-        .description("Create a button to register Discord users in MDMA")
-        .default_member_permissions(Permissions::ADMINISTRATOR)
-        .add_option(CreateCommandOption::new(
-            CommandOptionType::Role,
-            "assign_role",
-            "Assign a role to members after successfully registering",
-        ))
-        .execute(&discord_http, (Some(*discord_guild), None))
-        .await
-        .expect("/register_users");
-
-    CreateCommand::new("whois")
-        .description("Lookup a Discord user in MDMA")
-        .default_member_permissions(Permissions::ADMINISTRATOR)
-        .add_option(
-            CreateCommandOption::new(CommandOptionType::User, "user", "The user to look up")
-                .required(true),
-        )
-        .execute(&discord_http, (Some(*discord_guild), None))
-        .await
-        .expect("/whois");
-
-    CreateCommand::new("MDMA WhoIs User")
-        .kind(CommandType::User)
-        .default_member_permissions(Permissions::ADMINISTRATOR)
-        .execute(&discord_http, (Some(*discord_guild), None))
-        .await
-        .expect("user:whois");
-}
-
-#[derive(serde::Serialize)]
-struct InviteOptions {
-    max_age: u64,
-    max_uses: u8,
-    unique: bool,
-}
-
-impl Default for InviteOptions {
-    fn default() -> Self {
-        Self {
-            max_age: 604800,
-            max_uses: 1,
-            unique: true,
-        }
+pub async fn create_commands(state: &crate::AppState) -> Result<(), serenity::Error> {
+    let commands = [
+        CreateCommand::new("register_users")
+            .description("Create a button to register Discord users in MDMA")
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::Role,
+                "assign_role",
+                "Assign a role to members after successfully registering",
+            )),
+        CreateCommand::new("whois")
+            .description("Lookup a Discord user in MDMA")
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::User, "user", "The user to look up")
+                    .required(true),
+            ),
+        CreateCommand::new("membership").description("Check the status of your membership"),
+        members::create_command(),
+        CreateCommand::new("MDMA WhoIs User")
+            .kind(CommandType::User)
+            .default_member_permissions(Permissions::ADMINISTRATOR),
+    ];
+    for command in commands {
+        state.discord.create_command(command).await?;
     }
-}
-
-pub async fn create_invite(
-    reason: Option<&str>,
-    state: &crate::AppState,
-) -> Result<String, serenity::Error> {
-    Ok(state
-        .discord_http
-        .create_invite(
-            state
-                .secret_store
-                .get("DISCORD_INVITE_CHANNEL_ID")
-                .unwrap()
-                .parse::<ChannelId>()
-                .unwrap(),
-            &InviteOptions::default(),
-            reason,
-        )
-        .await?
-        .url())
+    Ok(())
 }
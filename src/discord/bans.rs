@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Deserialize;
+use serenity::model::prelude::*;
+
+use crate::db::audit_events::AuditEvent;
+
+const BANS_PAGE_SIZE: u8 = 250;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuildBanAction {
+    #[default]
+    Nothing,
+    Kick,
+    Ban,
+}
+
+pub async fn guild_bans(state: &crate::AppState) -> Result<Vec<Ban>, serenity::Error> {
+    let mut bans = Vec::new();
+    loop {
+        let page = state
+            .discord
+            .get_bans(bans.last().map(|ban: &Ban| ban.user.id), BANS_PAGE_SIZE)
+            .await?;
+        let done = page.len() < usize::from(BANS_PAGE_SIZE);
+        bans.extend(page);
+        if done {
+            return Ok(bans);
+        }
+    }
+}
+
+fn discord_user(discord: Option<Decimal>) -> Option<UserId> {
+    discord
+        .and_then(|discord| discord.to_u64())
+        .map(UserId::new)
+}
+
+pub async fn ban_linked_user(
+    member_id: i32,
+    discord: Option<Decimal>,
+    action: GuildBanAction,
+    reason: &str,
+    account_id: i32,
+    state: &crate::AppState,
+) -> Result<(), serenity::Error> {
+    let Some(user_id) = discord_user(discord) else {
+        return Ok(());
+    };
+    let audit_reason = format!("MDMA: {}", reason.trim());
+    let action = match action {
+        GuildBanAction::Nothing => return Ok(()),
+        GuildBanAction::Kick => {
+            state
+                .discord
+                .kick_member(user_id, Some(&audit_reason))
+                .await?;
+            "discord.kick"
+        }
+        GuildBanAction::Ban => {
+            state.discord.ban_user(user_id, Some(&audit_reason)).await?;
+            "discord.ban"
+        }
+    };
+
+    let _ = AuditEvent {
+        account_id: Some(account_id),
+        member_id: Some(member_id),
+        action,
+        reason: Some(reason.trim()).filter(|reason| !reason.is_empty()),
+        after: Some(serde_json::json!({ "discord": user_id.get() })),
+        ..Default::default()
+    }
+    .record(&state.db_pool)
+    .await;
+    Ok(())
+}
+
+pub async fn unban_linked_user(
+    member_id: i32,
+    discord: Option<Decimal>,
+    reason: &str,
+    account_id: i32,
+    state: &crate::AppState,
+) -> Result<(), serenity::Error> {
+    let Some(user_id) = discord_user(discord) else {
+        return Ok(());
+    };
+    state
+        .discord
+        .remove_ban(user_id, Some(&format!("MDMA: {}", reason.trim())))
+        .await?;
+
+    let _ = AuditEvent {
+        account_id: Some(account_id),
+        member_id: Some(member_id),
+        action: "discord.unban",
+        reason: Some(reason.trim()).filter(|reason| !reason.is_empty()),
+        after: Some(serde_json::json!({ "discord": user_id.get() })),
+        ..Default::default()
+    }
+    .record(&state.db_pool)
+    .await;
+    Ok(())
+}
+
+pub struct LinkedMember {
+    pub id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub discord: Decimal,
+    pub banned: bool,
+}
+
+pub struct BanMismatch {
+    pub user_id: UserId,
+    pub username: Option<String>,
+    pub ban_reason: Option<String>,
+    pub member: Option<LinkedMember>,
+}
+
+#[derive(Default)]
+pub struct BanReport {
+    pub discord_only: Vec<BanMismatch>,
+    pub mdma_only: Vec<BanMismatch>,
+}
+
+pub async fn ban_report(state: &crate::AppState) -> Result<BanReport, String> {
+    let bans = guild_bans(state).await.map_err(|err| err.to_string())?;
+    let members = sqlx::query_as!(
+        LinkedMember,
+        r#"SELECT id, first_name, last_name, email, discord AS "discord!", banned
+            FROM members
+            WHERE discord IS NOT NULL
+            ORDER BY banned DESC, id"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    let mut members_by_user = HashMap::<UserId, Vec<LinkedMember>>::new();
+    for member in members {
+        if let Some(user_id) = discord_user(Some(member.discord)) {
+            members_by_user.entry(user_id).or_default().push(member);
+        }
+    }
+    let mut report = BanReport::default();
+    for ban in bans {
+        let linked = members_by_user.remove(&ban.user.id).unwrap_or_default();
+        if linked.iter().any(|member| member.banned) {
+            continue;
+        }
+        report.discord_only.push(BanMismatch {
+            user_id: ban.user.id,
+            username: Some(ban.user.name),
+            ban_reason: ban.reason,
+            member: linked.into_iter().next(),
+        });
+    }
+    // Anyone left over is not banned in the guild
+    for (user_id, linked) in members_by_user {
+        if let Some(member) = linked.into_iter().find(|member| member.banned) {
+            report.mdma_only.push(BanMismatch {
+                user_id,
+                username: None,
+                ban_reason: None,
+                member: Some(member),
+            });
+        }
+    }
+    report
+        .mdma_only
+        .sort_by_key(|mismatch| mismatch.member.as_ref().map(|member| member.id));
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{discord::client::DiscordClient, insert_test_member};
+
+    async fn ban_members(member_ids: &[i32], db_pool: &sqlx::PgPool) {
+        sqlx::query!(
+            "UPDATE members SET banned = TRUE WHERE id = ANY($1)",
+            member_ids
+        )
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn kicks_and_bans_linked_users(db_pool: sqlx::PgPool) {
+        let (state, discord) = crate::AppState::with_mock_discord(db_pool);
+        let account_id = sqlx::query_scalar!(
+            "INSERT INTO accounts (email, is_admin) VALUES ('admin@example.com', TRUE) RETURNING id"
+        )
+        .fetch_one(&state.db_pool)
+        .await
+        .unwrap();
+        let kicked = insert_test_member("kicked@example.com", 10, &state.db_pool).await;
+        let banned = insert_test_member("banned@example.com", 11, &state.db_pool).await;
+        ban_members(&[kicked, banned], &state.db_pool).await;
+        discord.add_member(UserId::new(10), &[]);
+        discord.add_member(UserId::new(11), &[]);
+
+        for (member_id, discord, action) in [
+            (kicked, 10, GuildBanAction::Nothing),
+            (kicked, 10, GuildBanAction::Kick),
+            (banned, 11, GuildBanAction::Ban),
+        ] {
+            let discord = Some(Decimal::from(discord));
+            ban_linked_user(member_id, discord, action, " spam ", account_id, &state)
+                .await
+                .unwrap();
+        }
+        unban_linked_user(
+            banned,
+            Some(Decimal::from(11)),
+            "appeal",
+            account_id,
+            &state,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            discord.recorded_calls(),
+            vec![
+                "kick_member(10, Some(\"MDMA: spam\"))",
+                "ban_user(11, Some(\"MDMA: spam\"))",
+                "remove_ban(11, Some(\"MDMA: appeal\"))",
+            ]
+        );
+        let audited = sqlx::query!(
+            r#"SELECT member_id AS "member_id!", action, reason AS "reason!"
+                FROM audit_events
+                WHERE action LIKE 'discord.%'
+                ORDER BY id"#
+        )
+        .fetch_all(&state.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| (event.member_id, event.action, event.reason))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            audited,
+            vec![
+                (kicked, String::from("discord.kick"), String::from("spam")),
+                (banned, String::from("discord.ban"), String::from("spam")),
+                (
+                    banned,
+                    String::from("discord.unban"),
+                    String::from("appeal")
+                ),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn reports_ban_mismatches(db_pool: sqlx::PgPool) {
+        let (state, discord) = crate::AppState::with_mock_discord(db_pool);
+        let mdma_only = insert_test_member("mdma@example.com", 10, &state.db_pool).await;
+        let discord_only = insert_test_member("discord@example.com", 11, &state.db_pool).await;
+        let both = insert_test_member("both@example.com", 12, &state.db_pool).await;
+        insert_test_member("neither@example.com", 13, &state.db_pool).await;
+        ban_members(&[mdma_only, both], &state.db_pool).await;
+        discord.add_ban(UserId::new(11), "raiding");
+        discord.add_ban(UserId::new(12), "spam");
+        discord.add_ban(UserId::new(14), "unlinked");
+
+        let report = ban_report(&state).await.unwrap();
+        assert_eq!(
+            report
+                .discord_only
+                .iter()
+                .map(|mismatch| (
+                    mismatch.user_id.get(),
+                    mismatch.ban_reason.as_deref(),
+                    mismatch.member.as_ref().map(|member| member.id)
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (11, Some("raiding"), Some(discord_only)),
+                (14, Some("unlinked"), None)
+            ]
+        );
+        assert_eq!(
+            report
+                .mdma_only
+                .iter()
+                .map(|mismatch| (
+                    mismatch.user_id.get(),
+                    mismatch.member.as_ref().map(|member| member.id)
+                ))
+                .collect::<Vec<_>>(),
+            vec![(10, Some(mdma_only))]
+        );
+    }
+}
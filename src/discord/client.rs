@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serenity::{
+    builder::{Builder, CreateCommand, CreateMessage},
+    http::{Http, UserPagination},
+    model::prelude::*,
+    Error, Result,
+};
+use shuttle_runtime::SecretStore;
+
+use super::mock::MockDiscord;
+
+#[derive(serde::Serialize)]
+pub struct InviteOptions {
+    pub max_age: u32,
+    pub max_uses: u8,
+    pub unique: bool,
+}
+
+impl Default for InviteOptions {
+    fn default() -> Self {
+        Self {
+            max_age: 604800,
+            max_uses: 1,
+            unique: true,
+        }
+    }
+}
+
+#[async_trait]
+pub trait DiscordClient: Send + Sync {
+    fn guild_id(&self) -> GuildId;
+
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    fn recorded_calls(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    async fn create_command(&self, command: CreateCommand) -> Result<()>;
+    async fn get_guild_roles(&self) -> Result<Vec<Role>>;
+    async fn get_channels(&self) -> Result<Vec<GuildChannel>>;
+    async fn get_guild_members(&self, limit: u64, after: Option<UserId>) -> Result<Vec<Member>>;
+    async fn get_user(&self, user_id: UserId) -> Result<User>;
+    async fn add_member_role(
+        &self,
+        user_id: UserId,
+        role_id: RoleId,
+        reason: Option<&str>,
+    ) -> Result<()>;
+    async fn remove_member_role(
+        &self,
+        user_id: UserId,
+        role_id: RoleId,
+        reason: Option<&str>,
+    ) -> Result<()>;
+    async fn get_bans(&self, after: Option<UserId>, limit: u8) -> Result<Vec<Ban>>;
+    async fn kick_member(&self, user_id: UserId, reason: Option<&str>) -> Result<()>;
+    async fn ban_user(&self, user_id: UserId, reason: Option<&str>) -> Result<()>;
+    async fn remove_ban(&self, user_id: UserId, reason: Option<&str>) -> Result<()>;
+    async fn create_invite(
+        &self,
+        channel_id: ChannelId,
+        options: &InviteOptions,
+        reason: Option<&str>,
+    ) -> Result<RichInvite>;
+    async fn get_guild_invites(&self) -> Result<Vec<RichInvite>>;
+    async fn send_message(&self, channel_id: ChannelId, message: CreateMessage) -> Result<()>;
+}
+
+pub struct SerenityDiscord {
+    http: Arc<Http>,
+    guild: GuildId,
+}
+
+impl SerenityDiscord {
+    pub fn new(token: &str, application_id: ApplicationId, guild: GuildId) -> Self {
+        let http = Arc::new(Http::new(token));
+        http.set_application_id(application_id);
+        Self { http, guild }
+    }
+}
+
+#[async_trait]
+impl DiscordClient for SerenityDiscord {
+    fn guild_id(&self) -> GuildId {
+        self.guild
+    }
+
+    async fn create_command(&self, command: CreateCommand) -> Result<()> {
+        command
+            .execute(self.http.as_ref(), (Some(self.guild), None))
+            .await
+            .map(|_| ())
+    }
+
+    async fn get_guild_roles(&self) -> Result<Vec<Role>> {
+        self.http.get_guild_roles(self.guild).await
+    }
+
+    async fn get_channels(&self) -> Result<Vec<GuildChannel>> {
+        self.http.get_channels(self.guild).await
+    }
+
+    async fn get_guild_members(&self, limit: u64, after: Option<UserId>) -> Result<Vec<Member>> {
+        self.http
+            .get_guild_members(self.guild, Some(limit), after.map(|user_id| user_id.get()))
+            .await
+    }
+
+    async fn get_user(&self, user_id: UserId) -> Result<User> {
+        self.http.get_user(user_id).await
+    }
+
+    async fn add_member_role(
+        &self,
+        user_id: UserId,
+        role_id: RoleId,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.http
+            .add_member_role(self.guild, user_id, role_id, reason)
+            .await
+    }
+
+    async fn remove_member_role(
+        &self,
+        user_id: UserId,
+        role_id: RoleId,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.http
+            .remove_member_role(self.guild, user_id, role_id, reason)
+            .await
+    }
+
+    async fn get_bans(&self, after: Option<UserId>, limit: u8) -> Result<Vec<Ban>> {
+        self.http
+            .get_bans(self.guild, after.map(UserPagination::After), Some(limit))
+            .await
+    }
+
+    async fn kick_member(&self, user_id: UserId, reason: Option<&str>) -> Result<()> {
+        self.http.kick_member(self.guild, user_id, reason).await
+    }
+
+    async fn ban_user(&self, user_id: UserId, reason: Option<&str>) -> Result<()> {
+        self.http.ban_user(self.guild, user_id, 0, reason).await
+    }
+
+    async fn remove_ban(&self, user_id: UserId, reason: Option<&str>) -> Result<()> {
+        self.http.remove_ban(self.guild, user_id, reason).await
+    }
+
+    async fn create_invite(
+        &self,
+        channel_id: ChannelId,
+        options: &InviteOptions,
+        reason: Option<&str>,
+    ) -> Result<RichInvite> {
+        self.http.create_invite(channel_id, options, reason).await
+    }
+
+    async fn get_guild_invites(&self) -> Result<Vec<RichInvite>> {
+        self.http.get_guild_invites(self.guild).await
+    }
+
+    async fn send_message(&self, channel_id: ChannelId, message: CreateMessage) -> Result<()> {
+        channel_id
+            .send_message(self.http.as_ref(), message)
+            .await
+            .map(|_| ())
+    }
+}
+
+const DISABLED: Error = Error::Other("Discord support is disabled");
+
+pub struct DisabledDiscord;
+
+#[async_trait]
+impl DiscordClient for DisabledDiscord {
+    fn guild_id(&self) -> GuildId {
+        GuildId::new(1)
+    }
+
+    fn enabled(&self) -> bool {
+        false
+    }
+
+    async fn create_command(&self, _: CreateCommand) -> Result<()> {
+        Err(DISABLED)
+    }
+
+    async fn get_guild_roles(&self) -> Result<Vec<Role>> {
+        Err(DISABLED)
+    }
+
+    async fn get_channels(&self) -> Result<Vec<GuildChannel>> {
+        Err(DISABLED)
+    }
+
+    async fn get_guild_members(&self, _: u64, _: Option<UserId>) -> Result<Vec<Member>> {
+        Err(DISABLED)
+    }
+
+    async fn get_user(&self, _: UserId) -> Result<User> {
+        Err(DISABLED)
+    }
+
+    async fn add_member_role(&self, _: UserId, _: RoleId, _: Option<&str>) -> Result<()> {
+        Err(DISABLED)
+    }
+
+    async fn remove_member_role(&self, _: UserId, _: RoleId, _: Option<&str>) -> Result<()> {
+        Err(DISABLED)
+    }
+
+    async fn get_bans(&self, _: Option<UserId>, _: u8) -> Result<Vec<Ban>> {
+        Err(DISABLED)
+    }
+
+    async fn kick_member(&self, _: UserId, _: Option<&str>) -> Result<()> {
+        Err(DISABLED)
+    }
+
+    async fn ban_user(&self, _: UserId, _: Option<&str>) -> Result<()> {
+        Err(DISABLED)
+    }
+
+    async fn remove_ban(&self, _: UserId, _: Option<&str>) -> Result<()> {
+        Err(DISABLED)
+    }
+
+    async fn create_invite(
+        &self,
+        _: ChannelId,
+        _: &InviteOptions,
+        _: Option<&str>,
+    ) -> Result<RichInvite> {
+        Err(DISABLED)
+    }
+
+    async fn get_guild_invites(&self) -> Result<Vec<RichInvite>> {
+        Err(DISABLED)
+    }
+
+    async fn send_message(&self, _: ChannelId, _: CreateMessage) -> Result<()> {
+        Err(DISABLED)
+    }
+}
+
+// DISCORD_CLIENT: "mock", "disabled" or unset for the real API
+pub fn from_secrets(secret_store: &SecretStore) -> Arc<dyn DiscordClient> {
+    match secret_store.get("DISCORD_CLIENT").as_deref() {
+        Some("mock") => return Arc::new(MockDiscord::default()),
+        Some("disabled") => return Arc::new(DisabledDiscord),
+        _ => {}
+    }
+
+    let token = secret_store.get("DISCORD_BOT_TOKEN");
+    let application_id = secret_store
+        .get("DISCORD_APPLICATION_ID")
+        .and_then(|id| id.parse::<ApplicationId>().ok());
+    let guild = secret_store
+        .get("DISCORD_GUILD_ID")
+        .and_then(|id| id.parse::<GuildId>().ok());
+    match (token, application_id, guild) {
+        (Some(token), Some(application_id), Some(guild)) => {
+            Arc::new(SerenityDiscord::new(&token, application_id, guild))
+        }
+        _ => {
+            tracing::warn!("Discord secrets are missing or invalid, Discord support is disabled");
+            Arc::new(DisabledDiscord)
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use serenity::model::prelude::*;
+
+use crate::{
+    db::audit_events::AuditEvent,
+    send_email::{queue_email, EmailValues},
+};
+
+use super::client::InviteOptions;
+
+const INVITE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+pub async fn create_invite(
+    member_id: Option<i32>,
+    account_id: Option<i32>,
+    reason: Option<&str>,
+    state: &crate::AppState,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<String, String> {
+    let channel_id = state
+        .secret_store
+        .get("DISCORD_INVITE_CHANNEL_ID")
+        .and_then(|channel_id| channel_id.parse::<ChannelId>().ok())
+        .ok_or("DISCORD_INVITE_CHANNEL_ID is not set")?;
+    let invite = state
+        .discord
+        .create_invite(channel_id, &InviteOptions::default(), reason)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    sqlx::query!(
+        r#"INSERT INTO invites (code, member_id, account_id, reason, expires_at)
+            VALUES ($1, $2, $3, $4, CASE WHEN $5::INT > 0 THEN NOW() + make_interval(secs => $5::INT) END)"#,
+        invite.code,
+        member_id,
+        account_id,
+        reason,
+        i32::try_from(invite.max_age).unwrap_or(i32::MAX)
+    )
+    .execute(db)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    Ok(invite.url())
+}
+
+pub async fn send_welcome_email(
+    member_id: i32,
+    to_address: &str,
+    values: EmailValues,
+    reason: &str,
+    state: &crate::AppState,
+) {
+    let invite_url =
+        match create_invite(Some(member_id), None, Some(reason), state, &state.db_pool).await {
+            Ok(invite_url) => invite_url,
+            Err(err) => {
+                tracing::warn!(
+                    "Could not create an invite for member #{}: {}",
+                    member_id,
+                    err
+                );
+                if let Err(err) = (AuditEvent {
+                    member_id: Some(member_id),
+                    action: "member.discord_invite_failed",
+                    reason: Some(&err),
+                    ..Default::default()
+                })
+                .record(&state.db_pool)
+                .await
+                {
+                    tracing::warn!("Could not record the failed invite: {}", err);
+                }
+                return;
+            }
+        };
+
+    let queued = match state.db_pool.acquire().await {
+        Ok(mut conn) => {
+            queue_email(
+                "discord",
+                to_address,
+                &EmailValues {
+                    invite_url,
+                    ..values
+                },
+                Some(member_id),
+                &mut conn,
+            )
+            .await
+        }
+        Err(err) => Err(err.to_string()),
+    };
+    if let Err(err) = queued {
+        tracing::warn!(
+            "Could not queue the welcome email for member #{}: {}",
+            member_id,
+            err
+        );
+    }
+}
+
+pub async fn check_invites(state: &crate::AppState) -> Result<(), String> {
+    let guild_invites = state
+        .discord
+        .get_guild_invites()
+        .await
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .map(|invite| (invite.code, invite.uses))
+        .collect::<HashMap<_, _>>();
+
+    let pending = sqlx::query_scalar!("SELECT code FROM invites WHERE status = 'pending'")
+        .fetch_all(&state.db_pool)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let (mut used, mut gone) = (Vec::new(), Vec::new());
+    for code in pending {
+        match guild_invites.get(&code) {
+            Some(0) => {}
+            Some(_) => used.push(code),
+            None => gone.push(code),
+        }
+    }
+
+    sqlx::query!(
+        r#"UPDATE invites
+            SET
+                status = CASE
+                    WHEN code = ANY($1) THEN 'used'
+                    WHEN expires_at IS NOT NULL AND expires_at <= NOW() THEN 'expired'
+                    ELSE 'revoked'
+                END,
+                checked_at = NOW()
+            WHERE code = ANY($1) OR code = ANY($2)"#,
+        &used,
+        &gone
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    sqlx::query!("UPDATE invites SET checked_at = NOW() WHERE status = 'pending'")
+        .execute(&state.db_pool)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+pub async fn check_invites_periodically(state: crate::AppState) {
+    let mut interval = tokio::time::interval(INVITE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = check_invites(&state).await {
+            tracing::warn!("Checking Discord invites failed: {}", err);
+        }
+    }
+}
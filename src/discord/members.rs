@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use rust_decimal::Decimal;
+use serenity::{builder::*, model::prelude::*};
+use time::{macros::format_description, Date, OffsetDateTime};
+
+use crate::db::{
+    members::{MemberRow, MembersQuery},
+    payments::{NewPayment, PAYMENT_METHODS},
+};
+
+use super::ephemeral_message;
+
+fn payment_method_list() -> String {
+    PAYMENT_METHODS
+        .iter()
+        .map(|(method, _)| *method)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn linked_account(
+    user_id: UserId,
+    db_pool: &sqlx::PgPool,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT account_id
+            FROM account_discord_ids
+                INNER JOIN accounts ON accounts.id = account_id
+            WHERE discord = $1 AND is_admin"#,
+        Decimal::from(user_id.get())
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+fn not_linked() -> CreateInteractionResponse {
+    ephemeral_message(
+        "Your Discord account is not linked to an MDMA admin account. Ask an admin to link it under Settings.",
+    )
+}
+
+async fn member_by_id(
+    member_id: i64,
+    db_pool: &sqlx::PgPool,
+) -> Result<Option<MemberRow>, sqlx::Error> {
+    let Ok(member_id) = i32::try_from(member_id) else {
+        return Ok(None);
+    };
+    sqlx::query_as!(MemberRow, "SELECT * FROM members WHERE id = $1", member_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+async fn search(
+    text: &str,
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, StatusCode> {
+    let params = MembersQuery {
+        search: Some(text.to_owned()),
+        discord: None,
+        member_status: None,
+        discord_status: None,
+        count: 10,
+        offset: 0,
+        generation_id: -1,
+        plan_id: -1,
+        sort_by: String::new(),
+        sort_desc: false,
+    };
+    let members = crate::db::members::search(&params, state)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if members.is_empty() {
+        return Ok(ephemeral_message(format!(
+            "No members found for \"{}\"",
+            text
+        )));
+    }
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .embed(
+                CreateEmbed::new()
+                    .title(format!("Members matching \"{}\"", text))
+                    .description(
+                        members
+                            .iter()
+                            .map(|member| {
+                                format!(
+                                    "`#{}` **{}, {}** {}{}{}{}",
+                                    member.id,
+                                    member.last_name,
+                                    member.first_name,
+                                    member.email,
+                                    match member.discord {
+                                        Some(discord) => format!(" <@{}>", discord),
+                                        None => String::new(),
+                                    },
+                                    if member.cancelled { " (cancelled)" } else { "" },
+                                    if member.banned { " (banned)" } else { "" },
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    ),
+            ),
+    ))
+}
+
+async fn payment_modal(
+    member_id: i64,
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, StatusCode> {
+    let Some(member) = member_by_id(member_id, &state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        return Ok(ephemeral_message(format!(
+            "No member with ID {}",
+            member_id
+        )));
+    };
+
+    let mut title = format!("Add Payment: {}, {}", member.last_name, member.first_name);
+    if title.chars().count() > 45 {
+        title = title.chars().take(44).chain(std::iter::once('…')).collect();
+    }
+
+    Ok(CreateInteractionResponse::Modal(
+        CreateModal::new(format!("mdma_payment_modal:{}", member.id), title).components(vec![
+            CreateActionRow::InputText(
+                CreateInputText::new(
+                    InputTextStyle::Short,
+                    "Payment Method / Reason",
+                    "payment_method",
+                )
+                .placeholder(payment_method_list())
+                .value("cash")
+                .required(true),
+            ),
+            CreateActionRow::InputText(
+                CreateInputText::new(InputTextStyle::Short, "Amount Paid", "amount_paid")
+                    .value("0.00")
+                    .required(true),
+            ),
+            CreateActionRow::InputText(
+                CreateInputText::new(
+                    InputTextStyle::Short,
+                    "Effective On (YYYY-MM-DD)",
+                    "effective_on",
+                )
+                .value(OffsetDateTime::now_utc().date().to_string())
+                .required(true),
+            ),
+            CreateActionRow::InputText(
+                CreateInputText::new(
+                    InputTextStyle::Short,
+                    "Duration (Months)",
+                    "duration_months",
+                )
+                .value("1")
+                .required(true),
+            ),
+            CreateActionRow::InputText(
+                CreateInputText::new(InputTextStyle::Paragraph, "Notes", "notes").required(false),
+            ),
+        ]),
+    ))
+}
+
+fn parse_payment(values: &HashMap<String, String>) -> Result<NewPayment, String> {
+    let field = |name: &str| {
+        values
+            .get(name)
+            .map(|value| value.trim())
+            .unwrap_or_default()
+    };
+
+    let payment_method = field("payment_method").to_lowercase();
+    if !PAYMENT_METHODS
+        .iter()
+        .any(|(method, _)| *method == payment_method)
+    {
+        return Err(format!(
+            "Payment method must be one of: {}",
+            payment_method_list()
+        ));
+    }
+    let amount_paid = field("amount_paid")
+        .trim_start_matches('$')
+        .parse::<Decimal>()
+        .ok()
+        .filter(|amount| !amount.is_sign_negative())
+        .ok_or("Invalid amount paid")?;
+    let effective_on = Date::parse(
+        field("effective_on"),
+        format_description!("[year]-[month]-[day]"),
+    )
+    .map_err(|_| "Effective date must be formatted as YYYY-MM-DD")?;
+    let duration_months = field("duration_months")
+        .parse::<i32>()
+        .ok()
+        .filter(|duration| *duration > 0)
+        .ok_or("Invalid duration")?;
+
+    Ok(NewPayment {
+        payment_method,
+        amount_paid: Some(amount_paid),
+        transaction_id: None,
+        effective_on,
+        duration_months,
+        plan_id: None,
+        notes: Some(field("notes").to_owned()).filter(|notes| !notes.is_empty()),
+    })
+}
+
+pub async fn submit_payment(
+    member_id: &str,
+    values: &HashMap<String, String>,
+    user_id: UserId,
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, StatusCode> {
+    let Some(account_id) = linked_account(user_id, &state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        return Ok(not_linked());
+    };
+    let member_id = member_id
+        .parse::<i32>()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut payment = match parse_payment(values) {
+        Ok(payment) => payment,
+        Err(err) => return Ok(ephemeral_message(err)),
+    };
+    payment.plan_id = sqlx::query_scalar!("SELECT plan_for_amount($1)", payment.amount_paid)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Some(plan_id) = payment.plan_id {
+        if let Err(err) =
+            crate::db::plans::check_duration(plan_id, payment.duration_months, None, &state.db_pool)
+                .await
+        {
+            return Ok(ephemeral_message(err));
+        }
+    }
+
+    Ok(
+        match crate::db::payments::create(member_id, account_id, &payment, &state.db_pool).await {
+            Ok(_) => ephemeral_message(format!(
+                "Added a {} month payment of ${} for member #{}",
+                payment.duration_months,
+                payment.amount_paid.unwrap_or_default().round_dp(2),
+                member_id
+            )),
+            Err(err) => ephemeral_message(format!("Could not add payment: {}", err)),
+        },
+    )
+}
+
+async fn cancel(
+    member_id: i64,
+    reason: &str,
+    account_id: i32,
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, StatusCode> {
+    let Some(member) = member_by_id(member_id, &state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        return Ok(ephemeral_message(format!(
+            "No member with ID {}",
+            member_id
+        )));
+    };
+
+    Ok(
+        match crate::db::members::update_status(
+            member.id,
+            account_id,
+            "member.cancel",
+            reason,
+            Some(true),
+            None,
+            &state.db_pool,
+        )
+        .await
+        {
+            Ok(_) => ephemeral_message(format!(
+                "Cancelled {}, {} ({})",
+                member.last_name, member.first_name, member.email
+            )),
+            Err(err) => ephemeral_message(format!("Could not cancel member: {}", err)),
+        },
+    )
+}
+
+pub async fn handle_command(
+    event: &CommandInteraction,
+    state: &crate::AppState,
+) -> Result<CreateInteractionResponse, StatusCode> {
+    let subcommand = event.data.options.first().ok_or(StatusCode::BAD_REQUEST)?;
+    let CommandDataOptionValue::SubCommand(options) = &subcommand.value else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let options: HashMap<&str, &CommandDataOptionValue> = options
+        .iter()
+        .map(|o| (o.name.as_str(), &o.value))
+        .collect();
+
+    let Some(account_id) = linked_account(event.user.id, &state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        return Ok(not_linked());
+    };
+
+    match subcommand.name.as_str() {
+        "search" => match options.get("query") {
+            Some(CommandDataOptionValue::String(text)) => search(text, state).await,
+            _ => Err(StatusCode::BAD_REQUEST),
+        },
+        "add_payment" => match options.get("member_id") {
+            Some(CommandDataOptionValue::Integer(member_id)) => {
+                payment_modal(*member_id, state).await
+            }
+            _ => Err(StatusCode::BAD_REQUEST),
+        },
+        "cancel" => match (options.get("member_id"), options.get("reason")) {
+            (Some(CommandDataOptionValue::Integer(member_id)), reason) => {
+                let reason = match reason {
+                    Some(CommandDataOptionValue::String(reason)) => reason.as_str(),
+                    _ => "",
+                };
+                cancel(*member_id, reason, account_id, state).await
+            }
+            _ => Err(StatusCode::BAD_REQUEST),
+        },
+        _ => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("member")
+        .description("Manage members in MDMA")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "search",
+                "Search members by name or email",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "query", "Name or email")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "add_payment",
+                "Record a payment for a member",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "member_id",
+                    "The member's MDMA ID",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "cancel",
+                "Cancel a membership",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "member_id",
+                    "The member's MDMA ID",
+                )
+                .required(true),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "reason",
+                "Cancellation reason",
+            )),
+        )
+}
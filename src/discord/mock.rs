@@ -0,0 +1,271 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serenity::{
+    all::{CreateCommand, CreateMessage},
+    model::prelude::*,
+    Error, Result,
+};
+
+use super::client::{DiscordClient, InviteOptions};
+
+const MOCK_GUILD: GuildId = GuildId::new(1);
+const UNKNOWN_MEMBER: Error = Error::Other("Unknown Member");
+
+#[derive(Default)]
+struct MockGuild {
+    roles: Vec<Role>,
+    channels: Vec<GuildChannel>,
+    members: Vec<Member>,
+    bans: Vec<Ban>,
+    invites: Vec<RichInvite>,
+    calls: Vec<String>,
+}
+
+pub struct MockDiscord {
+    guild: Mutex<MockGuild>,
+}
+
+impl Default for MockDiscord {
+    fn default() -> Self {
+        let mut everyone = Role::default();
+        everyone.id = RoleId::new(MOCK_GUILD.get());
+        everyone.guild_id = MOCK_GUILD;
+        everyone.name = "@everyone".to_owned();
+
+        let mut general = GuildChannel::default();
+        general.id = ChannelId::new(2);
+        general.guild_id = MOCK_GUILD;
+        general.name = "general".to_owned();
+        general.kind = ChannelType::Text;
+
+        Self {
+            guild: Mutex::new(MockGuild {
+                roles: vec![everyone],
+                channels: vec![general],
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+impl MockDiscord {
+    fn record(&self, call: String) -> std::sync::MutexGuard<'_, MockGuild> {
+        tracing::info!("Mock Discord: {}", call);
+        let mut guild = self.guild.lock().unwrap();
+        guild.calls.push(call);
+        guild
+    }
+}
+
+#[cfg(test)]
+impl MockDiscord {
+    pub fn add_member(&self, user_id: UserId, roles: &[RoleId]) {
+        let mut member = Member::default();
+        member.guild_id = MOCK_GUILD;
+        member.user = mock_user(user_id);
+        member.roles = roles.to_vec();
+        self.guild.lock().unwrap().members.push(member);
+    }
+
+    pub fn add_ban(&self, user_id: UserId, reason: &str) {
+        let ban = serde_json::from_value(serde_json::json!({
+            "reason": reason,
+            "user": mock_user(user_id),
+        }))
+        .unwrap();
+        self.guild.lock().unwrap().bans.push(ban);
+    }
+}
+
+fn mock_user(user_id: UserId) -> User {
+    let mut user = User::default();
+    user.id = user_id;
+    user.name = format!("user{}", user_id);
+    user
+}
+
+fn page<T>(
+    mut items: Vec<T>,
+    id: impl Fn(&T) -> UserId,
+    after: Option<UserId>,
+    limit: usize,
+) -> Vec<T> {
+    items.sort_by_key(&id);
+    items
+        .into_iter()
+        .filter(|item| after.is_none_or(|after| id(item) > after))
+        .take(limit)
+        .collect()
+}
+
+#[async_trait]
+impl DiscordClient for MockDiscord {
+    fn guild_id(&self) -> GuildId {
+        MOCK_GUILD
+    }
+
+    fn recorded_calls(&self) -> Vec<String> {
+        self.guild.lock().unwrap().calls.clone()
+    }
+
+    async fn create_command(&self, command: CreateCommand) -> Result<()> {
+        let command = serde_json::to_value(&command)?;
+        let name = command["name"].as_str().unwrap_or_default();
+        drop(self.record(format!("create_command({})", name)));
+        Ok(())
+    }
+
+    async fn get_guild_roles(&self) -> Result<Vec<Role>> {
+        Ok(self.record("get_guild_roles()".to_owned()).roles.clone())
+    }
+
+    async fn get_channels(&self) -> Result<Vec<GuildChannel>> {
+        Ok(self.record("get_channels()".to_owned()).channels.clone())
+    }
+
+    async fn get_guild_members(&self, limit: u64, after: Option<UserId>) -> Result<Vec<Member>> {
+        let guild = self.record(format!("get_guild_members({}, {:?})", limit, after));
+        Ok(page(
+            guild.members.clone(),
+            |member| member.user.id,
+            after,
+            usize::try_from(limit).unwrap_or(usize::MAX),
+        ))
+    }
+
+    async fn get_user(&self, user_id: UserId) -> Result<User> {
+        let guild = self.record(format!("get_user({})", user_id));
+        Ok(guild
+            .members
+            .iter()
+            .map(|member| member.user.clone())
+            .find(|user| user.id == user_id)
+            .unwrap_or_else(|| mock_user(user_id)))
+    }
+
+    async fn add_member_role(
+        &self,
+        user_id: UserId,
+        role_id: RoleId,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        let mut guild = self.record(format!(
+            "add_member_role({}, {}, {:?})",
+            user_id, role_id, reason
+        ));
+        let member = guild
+            .members
+            .iter_mut()
+            .find(|member| member.user.id == user_id)
+            .ok_or(UNKNOWN_MEMBER)?;
+        if !member.roles.contains(&role_id) {
+            member.roles.push(role_id);
+        }
+        Ok(())
+    }
+
+    async fn remove_member_role(
+        &self,
+        user_id: UserId,
+        role_id: RoleId,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        let mut guild = self.record(format!(
+            "remove_member_role({}, {}, {:?})",
+            user_id, role_id, reason
+        ));
+        let member = guild
+            .members
+            .iter_mut()
+            .find(|member| member.user.id == user_id)
+            .ok_or(UNKNOWN_MEMBER)?;
+        member.roles.retain(|role| *role != role_id);
+        Ok(())
+    }
+
+    async fn get_bans(&self, after: Option<UserId>, limit: u8) -> Result<Vec<Ban>> {
+        let guild = self.record(format!("get_bans({:?}, {})", after, limit));
+        Ok(page(
+            guild.bans.clone(),
+            |ban| ban.user.id,
+            after,
+            usize::from(limit),
+        ))
+    }
+
+    async fn kick_member(&self, user_id: UserId, reason: Option<&str>) -> Result<()> {
+        let mut guild = self.record(format!("kick_member({}, {:?})", user_id, reason));
+        let count = guild.members.len();
+        guild.members.retain(|member| member.user.id != user_id);
+        if guild.members.len() == count {
+            return Err(UNKNOWN_MEMBER);
+        }
+        Ok(())
+    }
+
+    async fn ban_user(&self, user_id: UserId, reason: Option<&str>) -> Result<()> {
+        let mut guild = self.record(format!("ban_user({}, {:?})", user_id, reason));
+        let user = guild
+            .members
+            .iter()
+            .map(|member| member.user.clone())
+            .find(|user| user.id == user_id)
+            .unwrap_or_else(|| mock_user(user_id));
+        guild.members.retain(|member| member.user.id != user_id);
+        guild.bans.retain(|ban| ban.user.id != user_id);
+        guild.bans.push(serde_json::from_value(serde_json::json!({
+            "reason": reason,
+            "user": user,
+        }))?);
+        Ok(())
+    }
+
+    async fn remove_ban(&self, user_id: UserId, reason: Option<&str>) -> Result<()> {
+        let mut guild = self.record(format!("remove_ban({}, {:?})", user_id, reason));
+        guild.bans.retain(|ban| ban.user.id != user_id);
+        Ok(())
+    }
+
+    async fn create_invite(
+        &self,
+        channel_id: ChannelId,
+        options: &InviteOptions,
+        reason: Option<&str>,
+    ) -> Result<RichInvite> {
+        let mut guild = self.record(format!("create_invite({}, {:?})", channel_id, reason));
+        let channel = guild
+            .channels
+            .iter()
+            .find(|channel| channel.id == channel_id)
+            .map(|channel| channel.name.clone())
+            .unwrap_or_default();
+        let invite: RichInvite = serde_json::from_value(serde_json::json!({
+            "channel": { "id": channel_id, "name": channel, "type": 0 },
+            "code": uuid::Uuid::new_v4().simple().to_string()[..10],
+            "created_at": Timestamp::now(),
+            "max_age": options.max_age,
+            "max_uses": options.max_uses,
+            "temporary": false,
+            "uses": 0,
+        }))?;
+        guild.invites.push(invite.clone());
+        Ok(invite)
+    }
+
+    async fn get_guild_invites(&self) -> Result<Vec<RichInvite>> {
+        Ok(self
+            .record("get_guild_invites()".to_owned())
+            .invites
+            .clone())
+    }
+
+    async fn send_message(&self, channel_id: ChannelId, message: CreateMessage) -> Result<()> {
+        drop(self.record(format!(
+            "send_message({}, {})",
+            channel_id,
+            serde_json::to_string(&message)?
+        )));
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use rust_decimal::Decimal;
+use serenity::{builder::*, model::prelude::*};
+use time::OffsetDateTime;
+
+use super::get_discord_setting;
+
+const NOTIFY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+const MAX_ATTEMPTS: i32 = 5;
+
+pub const EVENTS: &[(&str, &str)] = &[
+    ("member.create", "New Members"),
+    ("member.cancel", "Cancellations"),
+    ("member.uncancel", "Uncancellations"),
+    ("member.ban", "Bans"),
+    ("member.unban", "Unbans"),
+];
+
+pub fn setting_key(event: &str) -> String {
+    format!("notify:{}", event)
+}
+
+pub async fn notification_channel(db_pool: &sqlx::PgPool) -> Option<ChannelId> {
+    get_discord_setting("notification_channel", db_pool)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|channel| channel.parse::<ChannelId>().ok())
+}
+
+pub async fn event_enabled(event: &str, db_pool: &sqlx::PgPool) -> bool {
+    get_discord_setting(&setting_key(event), db_pool)
+        .await
+        .ok()
+        .flatten()
+        .is_none_or(|enabled| enabled == "true")
+}
+
+struct PendingNotification {
+    id: i32,
+    event: String,
+    created_at: OffsetDateTime,
+    first_name: String,
+    last_name: String,
+    email: String,
+    discord: Option<Decimal>,
+    reason: Option<String>,
+    account_email: Option<String>,
+}
+
+fn notification_embed(notification: &PendingNotification, mdma_url: &str) -> CreateEmbed {
+    let (title, colour) = match notification.event.as_str() {
+        "member.create" => ("New Member", Colour::DARK_GREEN),
+        "member.cancel" => ("Membership Cancelled", Colour::ORANGE),
+        "member.uncancel" => ("Membership Uncancelled", Colour::BLUE),
+        "member.ban" => ("Member Banned", Colour::RED),
+        "member.unban" => ("Member Unbanned", Colour::DARK_BLUE),
+        _ => ("Member Updated", Colour::LIGHT_GREY),
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .colour(colour)
+        .url(format!(
+            "https://{mdma_url}/admin/members?{}",
+            serde_html_form::to_string([("search", &notification.email)]).unwrap_or_default()
+        ))
+        .field(
+            "Name",
+            format!("{} {}", notification.first_name, notification.last_name),
+            true,
+        )
+        .field("Email", notification.email.clone(), true)
+        .timestamp(
+            Timestamp::from_unix_timestamp(notification.created_at.unix_timestamp())
+                .unwrap_or_default(),
+        );
+    if let Some(discord) = notification.discord {
+        embed = embed.field("Discord", format!("<@{}>", discord), true);
+    }
+    if let Some(reason) = &notification.reason {
+        embed = embed.field("Reason", reason.clone(), false);
+    }
+    if let Some(account_email) = &notification.account_email {
+        embed = embed.footer(CreateEmbedFooter::new(format!("By {}", account_email)));
+    }
+    embed
+}
+
+pub async fn send_pending(state: &crate::AppState) -> Result<(), sqlx::Error> {
+    let Some(channel_id) = notification_channel(&state.db_pool).await else {
+        sqlx::query!("DELETE FROM discord_notifications")
+            .execute(&state.db_pool)
+            .await?;
+        return Ok(());
+    };
+
+    let pending = sqlx::query_as!(
+        PendingNotification,
+        r#"SELECT
+                discord_notifications.id,
+                discord_notifications.event,
+                discord_notifications.created_at,
+                members.first_name,
+                members.last_name,
+                members.email,
+                members.discord,
+                audit_events.reason AS "reason?",
+                accounts.email AS "account_email?"
+            FROM discord_notifications
+                INNER JOIN members ON members.id = discord_notifications.member_id
+                LEFT JOIN LATERAL (
+                    SELECT reason, account_id
+                    FROM audit_events
+                    WHERE
+                        audit_events.member_id = discord_notifications.member_id
+                        AND audit_events.created_at >= discord_notifications.created_at - INTERVAL '1 minute'
+                        AND (
+                            audit_events.action = discord_notifications.event
+                            OR (discord_notifications.event = 'member.create' AND audit_events.action LIKE '%.new_member')
+                        )
+                    ORDER BY audit_events.id
+                    LIMIT 1
+                ) audit_events ON TRUE
+                LEFT JOIN accounts ON accounts.id = audit_events.account_id
+            ORDER BY discord_notifications.id"#
+    )
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let mdma_url = state.secret_store.get("MDMA_URL").unwrap_or_default();
+    for notification in pending {
+        if event_enabled(&notification.event, &state.db_pool).await {
+            let sent = state
+                .discord
+                .send_message(
+                    channel_id,
+                    CreateMessage::new().embed(notification_embed(&notification, &mdma_url)),
+                )
+                .await;
+            if let Err(err) = sent {
+                tracing::warn!("Discord notification {} failed: {}", notification.id, err);
+                sqlx::query!(
+                    "UPDATE discord_notifications SET attempts = attempts + 1 WHERE id = $1",
+                    notification.id
+                )
+                .execute(&state.db_pool)
+                .await?;
+                sqlx::query!(
+                    "DELETE FROM discord_notifications WHERE id = $1 AND attempts >= $2",
+                    notification.id,
+                    MAX_ATTEMPTS
+                )
+                .execute(&state.db_pool)
+                .await?;
+                continue;
+            }
+        }
+
+        sqlx::query!(
+            "DELETE FROM discord_notifications WHERE id = $1",
+            notification.id
+        )
+        .execute(&state.db_pool)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn notify_periodically(state: crate::AppState) {
+    let mut interval = tokio::time::interval(NOTIFY_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = send_pending(&state).await {
+            tracing::warn!("Sending Discord notifications failed: {}", err);
+        }
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serenity::model::prelude::*;
+
+use crate::db::audit_events::AuditEvent;
+
+use super::get_discord_setting;
+
+const RECONCILE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+struct LinkedMember {
+    id: i32,
+    first_name: String,
+    last_name: String,
+    discord: Decimal,
+    generation_id: Option<i32>,
+    should_have_role: bool,
+}
+
+pub struct RoleChange {
+    pub member_id: i32,
+    pub member_name: String,
+    pub user_id: UserId,
+    pub username: String,
+    pub role_id: RoleId,
+    pub role_name: String,
+    pub add: bool,
+}
+
+pub struct GenerationRole {
+    pub generation_id: i32,
+    pub title: String,
+    pub role_id: RoleId,
+}
+
+pub struct SyncedRoles {
+    pub member_role: Option<RoleId>,
+    pub generations: Vec<GenerationRole>,
+}
+
+impl SyncedRoles {
+    pub fn is_empty(&self) -> bool {
+        self.member_role.is_none() && self.generations.is_empty()
+    }
+
+    fn managed(&self) -> impl Iterator<Item = (RoleId, &str)> {
+        self.member_role
+            .map(|role_id| (role_id, "Member"))
+            .into_iter()
+            .chain(
+                self.generations
+                    .iter()
+                    .map(|generation| (generation.role_id, generation.title.as_str())),
+            )
+    }
+
+    fn wanted(&self, generation_id: Option<i32>) -> Vec<RoleId> {
+        self.member_role
+            .into_iter()
+            .chain(
+                self.generations
+                    .iter()
+                    .filter(|generation| Some(generation.generation_id) == generation_id)
+                    .map(|generation| generation.role_id),
+            )
+            .collect()
+    }
+}
+
+pub async fn member_role(db_pool: &sqlx::PgPool) -> Option<RoleId> {
+    get_discord_setting("member_role", db_pool)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|role| role.parse::<RoleId>().ok())
+}
+
+pub async fn synced_roles(db_pool: &sqlx::PgPool) -> Result<SyncedRoles, sqlx::Error> {
+    let generations = sqlx::query!(
+        r#"SELECT id, title, discord_role AS "discord_role!"
+            FROM generations
+            WHERE discord_role IS NOT NULL
+            ORDER BY start_date"#
+    )
+    .fetch_all(db_pool)
+    .await?
+    .into_iter()
+    .filter_map(|generation| {
+        Some(GenerationRole {
+            generation_id: generation.id,
+            title: generation.title,
+            role_id: generation.discord_role.to_u64().map(RoleId::new)?,
+        })
+    })
+    .collect();
+
+    Ok(SyncedRoles {
+        member_role: member_role(db_pool).await,
+        generations,
+    })
+}
+
+pub async fn guild_members(state: &crate::AppState) -> Result<Vec<Member>, serenity::Error> {
+    let mut members = Vec::new();
+    loop {
+        let page = state
+            .discord
+            .get_guild_members(1000, members.last().map(|member: &Member| member.user.id))
+            .await?;
+        let done = page.len() < 1000;
+        members.extend(page);
+        if done {
+            return Ok(members);
+        }
+    }
+}
+
+pub async fn plan_changes(
+    roles: &SyncedRoles,
+    state: &crate::AppState,
+) -> Result<Vec<RoleChange>, String> {
+    let linked = sqlx::query_as!(
+        LinkedMember,
+        r#"SELECT
+                members.id,
+                first_name,
+                last_name,
+                discord AS "discord!",
+                generation_id,
+                (is_active(members.id) AND NOT cancelled AND NOT banned) AS "should_have_role!"
+            FROM members
+                LEFT JOIN member_generations ON members.id = member_generations.member_id
+            WHERE discord IS NOT NULL
+            ORDER BY members.id"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    let guild_members = guild_members(state)
+        .await
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .map(|member| (member.user.id, member))
+        .collect::<HashMap<_, _>>();
+
+    // A Discord account linked to several member records keeps the roles of an active one
+    let mut desired = HashMap::<UserId, &LinkedMember>::new();
+    for member in &linked {
+        let Some(user_id) = member.discord.to_u64().map(UserId::new) else {
+            continue;
+        };
+        match desired.get(&user_id) {
+            Some(existing) if existing.should_have_role => {}
+            _ => {
+                desired.insert(user_id, member);
+            }
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (user_id, member) in desired {
+        let Some(guild_member) = guild_members.get(&user_id) else {
+            continue;
+        };
+        let wanted = if member.should_have_role {
+            roles.wanted(member.generation_id)
+        } else {
+            Vec::new()
+        };
+        for (role_id, role_name) in roles.managed() {
+            let add = wanted.contains(&role_id);
+            if guild_member.roles.contains(&role_id) != add {
+                changes.push(RoleChange {
+                    member_id: member.id,
+                    member_name: format!("{}, {}", member.last_name, member.first_name),
+                    user_id,
+                    username: guild_member.user.name.clone(),
+                    role_id,
+                    role_name: role_name.to_owned(),
+                    add,
+                });
+            }
+        }
+    }
+    changes.sort_by_key(|change| (change.member_id, change.add));
+
+    Ok(changes)
+}
+
+pub async fn apply_changes(
+    changes: &[RoleChange],
+    account_id: Option<i32>,
+    state: &crate::AppState,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for change in changes {
+        let result = if change.add {
+            state
+                .discord
+                .add_member_role(
+                    change.user_id,
+                    change.role_id,
+                    Some("MDMA: membership is active"),
+                )
+                .await
+        } else {
+            state
+                .discord
+                .remove_member_role(
+                    change.user_id,
+                    change.role_id,
+                    Some("MDMA: membership or generation changed"),
+                )
+                .await
+        };
+
+        if let Err(err) = result {
+            errors.push(format!(
+                "{} ({}), {}: {}",
+                change.member_name, change.username, change.role_name, err
+            ));
+            continue;
+        }
+
+        let _ = AuditEvent {
+            account_id,
+            member_id: Some(change.member_id),
+            action: if change.add {
+                "discord.role_add"
+            } else {
+                "discord.role_remove"
+            },
+            after: Some(serde_json::json!({
+                "discord": change.user_id.get(),
+                "role": change.role_id.get(),
+                "role_name": change.role_name,
+            })),
+            ..Default::default()
+        }
+        .record(&state.db_pool)
+        .await;
+    }
+    errors
+}
+
+pub async fn reconcile(account_id: Option<i32>, state: &crate::AppState) -> Result<usize, String> {
+    let roles = synced_roles(&state.db_pool)
+        .await
+        .map_err(|err| err.to_string())?;
+    if roles.is_empty() {
+        return Ok(0);
+    }
+    let changes = plan_changes(&roles, state).await?;
+    let errors = apply_changes(&changes, account_id, state).await;
+    if errors.is_empty() {
+        Ok(changes.len())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+pub async fn assign_registered_roles(
+    member_id: i32,
+    user_id: UserId,
+    state: &crate::AppState,
+) -> Result<(), String> {
+    let roles = synced_roles(&state.db_pool)
+        .await
+        .map_err(|err| err.to_string())?;
+    let member = sqlx::query!(
+        r#"SELECT
+                generation_id,
+                (is_active(members.id) AND NOT cancelled AND NOT banned) AS "should_have_role!"
+            FROM members
+                LEFT JOIN member_generations ON members.id = member_generations.member_id
+            WHERE members.id = $1"#,
+        member_id
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+    if !member.should_have_role {
+        return Ok(());
+    }
+
+    for role_id in roles.wanted(member.generation_id) {
+        state
+            .discord
+            .add_member_role(user_id, role_id, Some("MDMA: registered member"))
+            .await
+            .map_err(|err| err.to_string())?;
+        let _ = AuditEvent {
+            member_id: Some(member_id),
+            action: "discord.role_add",
+            after: Some(serde_json::json!({
+                "discord": user_id.get(),
+                "role": role_id.get(),
+            })),
+            ..Default::default()
+        }
+        .record(&state.db_pool)
+        .await;
+    }
+    Ok(())
+}
+
+pub async fn reconcile_periodically(state: crate::AppState) {
+    let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = reconcile(None, &state).await {
+            tracing::warn!("Discord role reconciliation failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discord::{client::DiscordClient, insert_discord_setting};
+
+    const MEMBER_ROLE: RoleId = RoleId::new(100);
+    const OLD_GENERATION_ROLE: RoleId = RoleId::new(200);
+    const NEW_GENERATION_ROLE: RoleId = RoleId::new(201);
+
+    async fn paid_member(email: &str, discord: u64, db_pool: &sqlx::PgPool) -> i32 {
+        let member_id = crate::insert_test_member(email, discord, db_pool).await;
+        sqlx::query!(
+            "INSERT INTO payments (member_id, amount_paid, duration_months) VALUES ($1, 10, 1)",
+            member_id
+        )
+        .execute(db_pool)
+        .await
+        .unwrap();
+        member_id
+    }
+
+    async fn audit_actions(db_pool: &sqlx::PgPool) -> Vec<String> {
+        sqlx::query_scalar!(
+            "SELECT action FROM audit_events WHERE action LIKE 'discord.%' ORDER BY id"
+        )
+        .fetch_all(db_pool)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn syncs_member_role_with_membership(db_pool: sqlx::PgPool) {
+        let (state, discord) = crate::AppState::with_mock_discord(db_pool);
+        let active = paid_member("active@example.com", 10, &state.db_pool).await;
+        let lapsed = crate::insert_test_member("lapsed@example.com", 11, &state.db_pool).await;
+        paid_member("away@example.com", 12, &state.db_pool).await;
+        discord.add_member(UserId::new(10), &[]);
+        discord.add_member(UserId::new(11), &[MEMBER_ROLE]);
+        discord.add_member(UserId::new(13), &[MEMBER_ROLE]);
+        let roles = SyncedRoles {
+            member_role: Some(MEMBER_ROLE),
+            generations: Vec::new(),
+        };
+
+        let changes = plan_changes(&roles, &state).await.unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (change.member_id, change.role_id, change.add))
+                .collect::<Vec<_>>(),
+            vec![(active, MEMBER_ROLE, true), (lapsed, MEMBER_ROLE, false)]
+        );
+
+        assert!(apply_changes(&changes, None, &state).await.is_empty());
+        assert!(discord.recorded_calls().ends_with(&[
+            String::from("add_member_role(10, 100, Some(\"MDMA: membership is active\"))"),
+            String::from(
+                "remove_member_role(11, 100, Some(\"MDMA: membership or generation changed\"))"
+            ),
+        ]));
+        assert_eq!(
+            audit_actions(&state.db_pool).await,
+            vec!["discord.role_add", "discord.role_remove"]
+        );
+        assert!(plan_changes(&roles, &state).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn reports_failed_changes_without_auditing_them(db_pool: sqlx::PgPool) {
+        let (state, _) = crate::AppState::with_mock_discord(db_pool);
+        let member_id = paid_member("member@example.com", 10, &state.db_pool).await;
+        let changes = [RoleChange {
+            member_id,
+            member_name: String::from("member@example.com, Test"),
+            user_id: UserId::new(10),
+            username: String::from("user10"),
+            role_id: MEMBER_ROLE,
+            role_name: String::from("Member"),
+            add: true,
+        }];
+
+        let errors = apply_changes(&changes, None, &state).await;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("member@example.com, Test (user10), Member: "));
+        assert!(audit_actions(&state.db_pool).await.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn moves_members_to_their_generation_role(db_pool: sqlx::PgPool) {
+        let (state, discord) = crate::AppState::with_mock_discord(db_pool);
+        insert_discord_setting("member_role", &MEMBER_ROLE.to_string(), &state.db_pool)
+            .await
+            .unwrap();
+        sqlx::query!(
+            r#"INSERT INTO generations (title, start_date, discord_role)
+                VALUES ('Old', '2000-01-01', $1), ('New', '2020-01-01', $2)"#,
+            Decimal::from(OLD_GENERATION_ROLE.get()),
+            Decimal::from(NEW_GENERATION_ROLE.get())
+        )
+        .execute(&state.db_pool)
+        .await
+        .unwrap();
+        let member_id = paid_member("member@example.com", 10, &state.db_pool).await;
+        discord.add_member(UserId::new(10), &[MEMBER_ROLE, OLD_GENERATION_ROLE]);
+
+        let roles = synced_roles(&state.db_pool).await.unwrap();
+        let changes = plan_changes(&roles, &state).await.unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (change.member_id, change.role_name.as_str(), change.add))
+                .collect::<Vec<_>>(),
+            vec![(member_id, "Old", false), (member_id, "New", true)]
+        );
+
+        assert!(apply_changes(&changes, None, &state).await.is_empty());
+        assert!(plan_changes(&roles, &state).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serenity::model::prelude::*;
+use time::OffsetDateTime;
+
+const SYNC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+fn to_datetime(timestamp: Timestamp) -> Option<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp(timestamp.unix_timestamp()).ok()
+}
+
+pub async fn sync_users(state: &crate::AppState) -> Result<(), String> {
+    let roles = state
+        .discord
+        .get_guild_roles()
+        .await
+        .map_err(|err| err.to_string())?;
+    let members = super::roles::guild_members(state)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut transaction = state.db_pool.begin().await.map_err(|err| err.to_string())?;
+
+    let role_ids = roles
+        .iter()
+        .map(|role| Decimal::from(role.id.get()))
+        .collect::<Vec<_>>();
+    sqlx::query!(
+        r#"INSERT INTO discord_roles (id, name, colour, position)
+            SELECT * FROM UNNEST($1::NUMERIC[], $2::TEXT[], $3::INT[], $4::INT[])
+            ON CONFLICT (id) DO UPDATE SET
+                name = excluded.name,
+                colour = excluded.colour,
+                position = excluded.position"#,
+        &role_ids,
+        &roles
+            .iter()
+            .map(|role| role.name.clone())
+            .collect::<Vec<_>>(),
+        &roles
+            .iter()
+            .map(|role| i32::try_from(role.colour.0).unwrap_or_default())
+            .collect::<Vec<_>>(),
+        &roles
+            .iter()
+            .map(|role| i32::from(role.position))
+            .collect::<Vec<_>>()
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+    sqlx::query!(
+        "DELETE FROM discord_roles WHERE NOT (id = ANY($1))",
+        &role_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    let user_ids = members
+        .iter()
+        .map(|member| Decimal::from(member.user.id.get()))
+        .collect::<Vec<_>>();
+    sqlx::query!(
+        r#"INSERT INTO discord_users (id, username, display_name, avatar_url, roles, joined_at, in_guild, synced_at)
+            SELECT id, username, display_name, avatar_url, string_to_array(roles, ',')::NUMERIC[], joined_at, TRUE, NOW()
+            FROM UNNEST($1::NUMERIC[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TIMESTAMPTZ[])
+                AS synced (id, username, display_name, avatar_url, roles, joined_at)
+            ON CONFLICT (id) DO UPDATE SET
+                username = excluded.username,
+                display_name = excluded.display_name,
+                avatar_url = excluded.avatar_url,
+                roles = excluded.roles,
+                joined_at = excluded.joined_at,
+                in_guild = TRUE,
+                synced_at = NOW()"#,
+        &user_ids,
+        &members
+            .iter()
+            .map(|member| member.user.name.clone())
+            .collect::<Vec<_>>(),
+        &members
+            .iter()
+            .map(|member| member.display_name().to_owned())
+            .collect::<Vec<_>>(),
+        &members.iter().map(|member| member.face()).collect::<Vec<_>>(),
+        &members
+            .iter()
+            .map(|member| {
+                member
+                    .roles
+                    .iter()
+                    .map(|role| role.get().to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>(),
+        &members
+            .iter()
+            .map(|member| member.joined_at.and_then(to_datetime))
+            .collect::<Vec<_>>() as _
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+    sqlx::query!(
+        "UPDATE discord_users SET in_guild = FALSE, roles = '{}', synced_at = NOW() WHERE in_guild AND NOT (id = ANY($1))",
+        &user_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    transaction.commit().await.map_err(|err| err.to_string())?;
+
+    // Linked accounts outside the guild still need a name and avatar, refreshed daily. Failed
+    // lookups back off exponentially so deleted accounts don't cost a request every sync
+    let lookups = sqlx::query_scalar!(
+        r#"SELECT DISTINCT members.discord AS "discord!"
+            FROM members
+                LEFT JOIN discord_users ON discord_users.id = members.discord
+                LEFT JOIN discord_user_lookups ON discord_user_lookups.id = members.discord
+            WHERE members.discord IS NOT NULL
+                AND (discord_users.id IS NULL OR (NOT discord_users.in_guild AND discord_users.synced_at < NOW() - INTERVAL '1 day'))
+                AND (discord_user_lookups.retry_at IS NULL OR discord_user_lookups.retry_at <= NOW())"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+    for discord in lookups {
+        let Some(user_id) = discord.to_u64().map(UserId::new) else {
+            continue;
+        };
+        let Ok(user) = state.discord.get_user(user_id).await else {
+            sqlx::query!(
+                r#"INSERT INTO discord_user_lookups (id, failures, retry_at)
+                    VALUES ($1, 1, NOW() + INTERVAL '1 hour')
+                    ON CONFLICT (id) DO UPDATE SET
+                        failures = discord_user_lookups.failures + 1,
+                        retry_at = NOW() + INTERVAL '1 hour' * 2 ^ LEAST(discord_user_lookups.failures, 8)"#,
+                discord
+            )
+            .execute(&state.db_pool)
+            .await
+            .map_err(|err| err.to_string())?;
+            continue;
+        };
+        sqlx::query!(
+            r#"INSERT INTO discord_users (id, username, display_name, avatar_url, in_guild)
+                VALUES ($1, $2, $3, $4, FALSE)
+                ON CONFLICT (id) DO UPDATE SET
+                    username = excluded.username,
+                    display_name = excluded.display_name,
+                    avatar_url = excluded.avatar_url,
+                    synced_at = NOW()
+                WHERE NOT discord_users.in_guild"#,
+            discord,
+            user.name,
+            user.global_name.as_deref().unwrap_or(&user.name),
+            user.face()
+        )
+        .execute(&state.db_pool)
+        .await
+        .map_err(|err| err.to_string())?;
+        sqlx::query!("DELETE FROM discord_user_lookups WHERE id = $1", discord)
+            .execute(&state.db_pool)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+pub async fn sync_users_periodically(state: crate::AppState) {
+    let mut interval = tokio::time::interval(SYNC_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = sync_users(&state).await {
+            tracing::warn!("Syncing Discord users failed: {}", err);
+        }
+    }
+}
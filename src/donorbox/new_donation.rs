@@ -1,14 +1,13 @@
 use axum::{extract::State, response::Response, Json};
-use lettre::AsyncTransport;
 use reqwest::StatusCode;
 use rust_decimal::Decimal;
 use time::OffsetDateTime;
-use tokio::try_join;
 
 use crate::{
-    discord::create_invite,
+    db::audit_events::AuditEvent,
+    discord::invites::send_welcome_email,
     err_responses::{ErrorResponse, MapErrorResponse},
-    send_email::{build_mailer, build_message, get_email_address, EmailValues},
+    send_email::{get_email_address, queue_email, EmailValues},
 };
 
 #[derive(serde::Deserialize)]
@@ -50,24 +49,10 @@ pub struct ResponseBody {
     inserted_transaction: InsertTransactionResult,
 }
 
-async fn send_emails(state: &crate::AppState, event: &DonationEvent) -> Result<(), Response> {
-    let invite_url = create_invite(
-        Some(&format!(
-            "New member automated invite (Donorbox transaction #{}, Email {})",
-            event.id, event.donor.email
-        )),
-        state,
-    )
-    .await
-    .map_err_response(ErrorResponse::InternalServerError)?;
-
-    let mailer = build_mailer(state)
-        .await
-        .map_err_response(ErrorResponse::InternalServerError)?;
-    let values = EmailValues {
+fn email_values(event: &DonationEvent) -> EmailValues {
+    EmailValues {
         first_name: event.donor.first_name.clone(),
         last_name: event.donor.last_name.clone(),
-        invite_url,
         email: event.donor.email.clone(),
         timestamp: event.donation_date.to_string(),
         amount_paid: event.formatted_net_amount.clone(),
@@ -86,38 +71,8 @@ async fn send_emails(state: &crate::AppState, event: &DonationEvent) -> Result<(
             event.stripe_charge_id
         ),
         referral_source: event.questions.get(0).cloned().unwrap_or_default().answer,
-    };
-
-    let board_notif_address = get_email_address("board_notif", &state.db_pool)
-        .await
-        .map_err_response(ErrorResponse::InternalServerError)?;
-    let board_notif_future = mailer.send(
-        build_message(
-            "board_notif",
-            "New Member Notification",
-            &board_notif_address,
-            &values,
-            state,
-        )
-        .await
-        .map_err_response(ErrorResponse::InternalServerError)?,
-    );
-
-    let discord_future = mailer.send(
-        build_message(
-            "discord",
-            "Psychedelic Club Discord",
-            &event.donor.email,
-            &values,
-            state,
-        )
-        .await
-        .map_err_response(ErrorResponse::InternalServerError)?,
-    );
-
-    try_join!(discord_future, board_notif_future)
-        .map_err_response(ErrorResponse::InternalServerError)?;
-    Ok(())
+        ..Default::default()
+    }
 }
 
 pub async fn process_donation(
@@ -137,36 +92,85 @@ pub async fn process_donation(
             .map_err_response(ErrorResponse::StatusCode(StatusCode::NO_CONTENT));
     }
 
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
     let created_member_id = sqlx::query_scalar!(
         "INSERT INTO members (email, first_name, last_name)
         SELECT $1, $2, $3
-        WHERE NOT EXISTS (SELECT * FROM members WHERE email = $1)
+        WHERE member_id_by_email($1) IS NULL
         ON CONFLICT DO NOTHING
         RETURNING id",
         event.donor.email,
         event.donor.first_name,
         event.donor.last_name
     )
-    .fetch_optional(&state.db_pool)
+    .fetch_optional(&mut *transaction)
     .await
     .map_err_response(ErrorResponse::InternalServerError)?;
 
     let inserted_transaction = sqlx::query_as!(
         InsertTransactionResult,
-        r#"INSERT INTO payments (member_id, amount_paid, payment_method, transaction_id, effective_on)
-            SELECT               id,        $2,          'donorbox',     $3,             $4
+        r#"INSERT INTO payments (member_id, amount_paid, duration_months,         plan_id,             payment_method, transaction_id, effective_on)
+            SELECT               id,        $2,          duration_for_amount($2), plan_for_amount($2), 'donorbox',     $3,             $4
             FROM members
-            WHERE email = $1
+            WHERE id = member_id_by_email($1)
                 AND NOT EXISTS (SELECT 1 FROM payments WHERE payment_method = 'donorbox' AND transaction_id = $3)
         RETURNING id, member_id"#,
         event.donor.email,
         event.net_amount,
         event.id,
         event.donation_date.date()
-    ).fetch_one(&state.db_pool).await.map_err_response(ErrorResponse::InternalServerError)?;
+    ).fetch_one(&mut *transaction).await.map_err_response(ErrorResponse::InternalServerError)?;
+
+    AuditEvent {
+        member_id: Some(inserted_transaction.member_id),
+        action: "donorbox.donation",
+        reason: Some(&format!("Donorbox donation #{}", event.id)),
+        after: Some(serde_json::json!({
+            "created_member": created_member_id.is_some(),
+            "payment_id": inserted_transaction.id,
+            "amount_paid": event.net_amount,
+        })),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+    let welcome_member_id = created_member_id.filter(|_| allow_email);
+    if let Some(member_id) = welcome_member_id {
+        let board_notif_address = get_email_address("board_notif", &mut *transaction)
+            .await
+            .map_err_response(ErrorResponse::InternalServerError)?;
+        queue_email(
+            "board_notif",
+            &board_notif_address,
+            &email_values(event),
+            Some(member_id),
+            &mut transaction,
+        )
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
+    }
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
 
-    if allow_email && created_member_id.is_some() {
-        send_emails(&state, &event).await?;
+    if let Some(member_id) = welcome_member_id {
+        send_welcome_email(
+            member_id,
+            &event.donor.email,
+            email_values(event),
+            &format!(
+                "New member automated invite (Donorbox transaction #{}, Email {})",
+                event.id, event.donor.email
+            ),
+            state,
+        )
+        .await;
     }
 
     Ok(ResponseBody {
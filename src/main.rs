@@ -1,4 +1,4 @@
-use std::{str::FromStr, sync::Arc};
+use std::sync::Arc;
 
 use axum::{
     response::{IntoResponse, Redirect, Response},
@@ -27,9 +27,47 @@ struct AppState {
     secret_store: SecretStore,
     google_oauth: oauth2::basic::BasicClient,
     http_client: reqwest::Client,
-    discord_verifier: serenity::interactions_endpoint::Verifier,
-    discord_http: Arc<serenity::http::Http>,
-    discord_guild: serenity::model::id::GuildId,
+    discord_verifier: Option<serenity::interactions_endpoint::Verifier>,
+    discord: Arc<dyn discord::client::DiscordClient>,
+    mail_transport: send_email::transport::MailTransport,
+}
+
+#[cfg(test)]
+impl AppState {
+    fn for_tests(db_pool: sqlx::PgPool, discord: Arc<dyn discord::client::DiscordClient>) -> Self {
+        Self {
+            db_pool,
+            secret_store: SecretStore::new(Default::default()),
+            google_oauth: auth::oauth_client(
+                String::new(),
+                String::new(),
+                String::from("http://localhost/oauth"),
+            ),
+            http_client: reqwest::Client::new(),
+            discord_verifier: None,
+            discord,
+            mail_transport: send_email::transport::MailTransport::Memory(
+                lettre::transport::stub::AsyncStubTransport::new_ok(),
+            ),
+        }
+    }
+
+    fn with_mock_discord(db_pool: sqlx::PgPool) -> (Self, Arc<discord::mock::MockDiscord>) {
+        let discord = Arc::new(discord::mock::MockDiscord::default());
+        (Self::for_tests(db_pool, discord.clone()), discord)
+    }
+}
+
+#[cfg(test)]
+async fn insert_test_member(email: &str, discord: u64, db_pool: &sqlx::PgPool) -> i32 {
+    sqlx::query_scalar!(
+        "INSERT INTO members (email, first_name, last_name, discord) VALUES ($1, 'Test', $1, $2) RETURNING id",
+        email,
+        rust_decimal::Decimal::from(discord)
+    )
+    .fetch_one(db_pool)
+    .await
+    .unwrap()
 }
 
 async fn home(cookies: CookieJar) -> Response {
@@ -65,25 +103,16 @@ async fn main(
         secret_store.get("GOOGLE_OAUTH_REDIRECT").unwrap(),
     );
 
-    let discord_verifier = serenity::interactions_endpoint::Verifier::new(
-        &secret_store.get("DISCORD_API_KEY").unwrap(),
-    );
-
     let http_client = reqwest::Client::new();
 
-    let discord_http = Arc::new(serenity::http::Http::new(
-        &secret_store.get("DISCORD_BOT_TOKEN").unwrap(),
-    ));
-    discord_http.set_application_id(
-        serenity::model::id::ApplicationId::from_str(
-            &secret_store.get("DISCORD_APPLICATION_ID").unwrap(),
-        )
-        .unwrap(),
-    );
+    let mail_transport = send_email::transport::MailTransport::from_secrets(&secret_store)
+        .expect("Invalid mail transport configuration");
 
-    let discord_guild =
-        serenity::model::id::GuildId::from_str(&secret_store.get("DISCORD_GUILD_ID").unwrap())
-            .unwrap();
+    let discord = discord::client::from_secrets(&secret_store);
+    let discord_verifier = secret_store
+        .get("DISCORD_API_KEY")
+        .filter(|_| discord.enabled())
+        .map(|key| serenity::interactions_endpoint::Verifier::new(&key));
 
     let state = AppState {
         db_pool,
@@ -91,11 +120,20 @@ async fn main(
         google_oauth,
         http_client,
         discord_verifier,
-        discord_http,
-        discord_guild,
+        discord,
+        mail_transport,
     };
 
-    discord::create_commands(&state).await;
+    tokio::spawn(send_email::outbox::deliver_periodically(state.clone()));
+    if state.discord.enabled() {
+        if let Err(err) = discord::create_commands(&state).await {
+            tracing::warn!("Registering Discord commands failed: {}", err);
+        }
+        tokio::spawn(discord::roles::reconcile_periodically(state.clone()));
+        tokio::spawn(discord::notifications::notify_periodically(state.clone()));
+        tokio::spawn(discord::invites::check_invites_periodically(state.clone()));
+        tokio::spawn(discord::users::sync_users_periodically(state.clone()));
+    }
 
     let router = Router::new()
         .route("/", get(home))
@@ -1,18 +1,20 @@
 use lettre::{
-    message::{Mailbox, SinglePart},
-    transport::smtp::authentication::{Credentials, Mechanism},
-    AsyncSmtpTransport, Message, Tokio1Executor,
+    message::{Mailbox, MultiPart, SinglePart},
+    Message,
 };
-use oauth2::{AccessToken, RefreshToken, TokenResponse};
 use serde::{Deserialize, Serialize};
 use tinytemplate::TinyTemplate;
 
+pub mod outbox;
+pub mod transport;
+
 #[derive(Deserialize, Serialize, Default)]
 #[serde(default)]
 pub struct EmailValues {
     pub first_name: String,
     pub last_name: String,
     pub invite_url: String,
+    pub verification_code: String,
     pub email: String,
     pub timestamp: String,
     pub amount_paid: String,
@@ -27,22 +29,121 @@ pub struct EmailValues {
     pub referral_source: String,
 }
 
-pub async fn get_email_template(id: &str, db_pool: &sqlx::PgPool) -> Result<String, sqlx::Error> {
-    sqlx::query_scalar!("SELECT template FROM email_templates WHERE id = $1", id)
-        .fetch_one(db_pool)
-        .await
+impl EmailValues {
+    pub fn sample() -> Self {
+        Self {
+            first_name: String::from("Alex"),
+            last_name: String::from("Example"),
+            invite_url: String::from("https://discord.gg/example"),
+            verification_code: String::from("123456"),
+            email: String::from("alex@example.com"),
+            timestamp: time::OffsetDateTime::now_utc().to_string(),
+            amount_paid: String::from("$25.00"),
+            donor_id: String::from("1001"),
+            donor_url: String::from("https://donorbox.org/org_admin/supporters/1001"),
+            donation_id: String::from("2002"),
+            donation_url: String::from("https://donorbox.org/org_admin/donations/2002"),
+            plan_id: String::from("3003"),
+            plan_url: String::from("https://donorbox.org/org_admin/plans/3003"),
+            payment_id: String::from("4004"),
+            payment_url: String::from("https://manage.webconnex.com/payments/4004"),
+            referral_source: String::from("A friend"),
+        }
+    }
+
+    fn field_names() -> Vec<String> {
+        match serde_json::to_value(Self::default()) {
+            Ok(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
-pub async fn insert_email_template(
+pub const BUILTIN_EMAIL_TEMPLATES: &[&str] = &["discord", "discord_verification", "board_notif"];
+
+#[derive(Serialize, Default)]
+pub struct EmailTemplate {
+    pub id: String,
+    pub subject: String,
+    pub description: String,
+    pub template: String,
+    pub plaintext: Option<String>,
+}
+
+pub async fn get_email_template(
     id: &str,
-    template: &str,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<EmailTemplate, sqlx::Error> {
+    sqlx::query_as!(
+        EmailTemplate,
+        "SELECT id, subject, description, template, plaintext FROM email_templates WHERE id = $1",
+        id
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn list_email_templates(
     db_pool: &sqlx::PgPool,
+) -> Result<Vec<EmailTemplate>, sqlx::Error> {
+    sqlx::query_as!(
+        EmailTemplate,
+        "SELECT id, subject, description, template, plaintext FROM email_templates ORDER BY id"
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn create_email_template(
+    email_template: &EmailTemplate,
+    db: impl sqlx::PgExecutor<'_>,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query_scalar!(
-        "INSERT INTO email_templates (id, template) VALUES ($1, $2) ON CONFLICT (id) DO UPDATE SET template = excluded.template",
+    sqlx::query!(
+        r#"INSERT INTO email_templates (id, subject, description, template, plaintext)
+            VALUES ($1, $2, $3, $4, $5)"#,
+        email_template.id,
+        email_template.subject,
+        email_template.description,
+        email_template.template,
+        email_template.plaintext
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn update_email_template(
+    id: &str,
+    email_template: &EmailTemplate,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE email_templates
+            SET id = $2, subject = $3, description = $4, template = $5, plaintext = $6
+            WHERE id = $1"#,
         id,
-        template
-    ).execute(db_pool).await.map(|_| ())
+        email_template.id,
+        email_template.subject,
+        email_template.description,
+        email_template.template,
+        email_template.plaintext
+    )
+    .execute(db)
+    .await
+    .and_then(|result| match result.rows_affected() {
+        0 => Err(sqlx::Error::RowNotFound),
+        _ => Ok(()),
+    })
+}
+
+pub async fn delete_email_template(
+    id: &str,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM email_templates WHERE id = $1", id)
+        .execute(db)
+        .await
+        .map(|_| ())
 }
 
 pub async fn insert_email_address(
@@ -57,9 +158,12 @@ pub async fn insert_email_address(
     ).execute(db_pool).await.map(|_| ())
 }
 
-pub async fn get_email_address(id: &str, db_pool: &sqlx::PgPool) -> Result<String, sqlx::Error> {
+pub async fn get_email_address(
+    id: &str,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<String, sqlx::Error> {
     sqlx::query_scalar!("SELECT value FROM email_addresses WHERE id = $1", id)
-        .fetch_one(db_pool)
+        .fetch_one(db)
         .await
 }
 
@@ -70,7 +174,7 @@ pub fn sanitize_email(contents: &str) -> String {
         .to_string()
 }
 
-fn populate_email_template(
+pub fn populate_email_template(
     template: &str,
     values: &EmailValues,
 ) -> Result<String, tinytemplate::error::Error> {
@@ -81,65 +185,211 @@ fn populate_email_template(
         .map(|populated| sanitize_email(&populated))
 }
 
-async fn get_access_token(state: &crate::AppState) -> Result<AccessToken, String> {
-    state
-        .google_oauth
-        .exchange_refresh_token(&RefreshToken::new(
-            state.secret_store.get("GMAIL_OAUTH_REFRESH_TOKEN").unwrap(),
-        ))
-        .request_async(oauth2::reqwest::async_http_client)
-        .await
-        .map(|resp| resp.access_token().to_owned())
+fn populate_plaintext_template(
+    template: &str,
+    values: &EmailValues,
+) -> Result<String, tinytemplate::error::Error> {
+    let mut templ = TinyTemplate::new();
+    templ.set_default_formatter(&tinytemplate::format_unescaped);
+    templ.add_template("email_template", template)?;
+    templ.render("email_template", values)
+}
+
+pub fn validate_email_template(template: &str) -> Result<(), String> {
+    TinyTemplate::new()
+        .add_template("email_template", template)
+        .map_err(|err| err.to_string())?;
+
+    let known = EmailValues::field_names();
+    let mut bound = Vec::new();
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let escaped = rest[..start].ends_with('\\');
+        rest = &rest[start..];
+        if escaped {
+            rest = &rest[1..];
+            continue;
+        }
+        let close = if rest.starts_with("{{") {
+            "}}"
+        } else if rest.starts_with("{#") {
+            "#}"
+        } else {
+            "}"
+        };
+        let Some(end) = rest.find(close) else {
+            break;
+        };
+        let tag = rest[close.len()..end].trim_matches(|c: char| c == '-' || c.is_whitespace());
+        let path = match (close, tag.split_whitespace().collect::<Vec<_>>().as_slice()) {
+            ("}}", ["if", "not", path] | ["if", path] | ["call", _, "with", path]) => Some(*path),
+            ("}}", ["with", path, "as", name] | ["for", name, "in", path]) => {
+                bound.push(*name);
+                Some(*path)
+            }
+            ("}", _) => tag.split('|').next().map(str::trim),
+            _ => None,
+        };
+        if let Some(field) = path.and_then(|path| path.split('.').next()) {
+            if !field.starts_with('@')
+                && !known.iter().any(|name| name == field)
+                && !bound.contains(&field)
+                && !unknown.contains(&field)
+            {
+                unknown.push(field);
+            }
+        }
+        rest = &rest[end + close.len()..];
+    }
+    if !unknown.is_empty() {
+        return Err(format!(
+            "Unknown field(s) {}. Available fields are {}",
+            unknown.join(", "),
+            known.join(", ")
+        ));
+    }
+
+    // Catches problems that only appear while rendering, like an unknown formatter
+    populate_email_template(template, &EmailValues::sample())
+        .map(|_| ())
         .map_err(|err| err.to_string())
 }
 
-pub async fn build_mailer(
-    state: &crate::AppState,
-) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
-    Ok(
-        AsyncSmtpTransport::<Tokio1Executor>::relay("smtp.gmail.com")
-            .map_err(|err| err.to_string())?
-            .authentication(vec![Mechanism::Xoauth2])
-            .credentials(Credentials::new(
-                state.secret_store.get("GMAIL_USERNAME").unwrap(),
-                get_access_token(state).await?.secret().clone(),
-            ))
-            .build(),
+pub struct EmailSender {
+    from: Mailbox,
+    reply_to: Mailbox,
+}
+
+pub async fn get_email_sender(db: impl sqlx::PgExecutor<'_>) -> Result<EmailSender, String> {
+    let addresses = sqlx::query!(
+        r#"SELECT
+                (SELECT value FROM email_addresses WHERE id = 'from') AS from_address,
+                (SELECT value FROM email_addresses WHERE id = 'replyto') AS replyto_address"#
     )
+    .fetch_one(db)
+    .await
+    .map_err(|err| err.to_string())?;
+    let mailbox = |address: Option<String>, id: &str| {
+        address
+            .ok_or(format!("The {} address is not set", id))?
+            .parse::<Mailbox>()
+            .map_err(|err| err.to_string())
+    };
+    Ok(EmailSender {
+        from: mailbox(addresses.from_address, "from")?,
+        reply_to: mailbox(addresses.replyto_address, "replyto")?,
+    })
 }
 
-pub async fn build_message(
-    email_key: &str,
-    subject: &str,
+pub fn build_message(
+    email_template: &EmailTemplate,
+    sender: &EmailSender,
     to_address: &str,
     values: &EmailValues,
-    state: &crate::AppState,
 ) -> Result<Message, String> {
-    let from_mbox = get_email_address("from", &state.db_pool)
-        .await
-        .map_err(|err| err.to_string())?
-        .parse::<Mailbox>()
-        .map_err(|err| err.to_string())?;
-    let replyto_mbox = get_email_address("replyto", &state.db_pool)
-        .await
-        .map_err(|err| err.to_string())?
-        .parse::<Mailbox>()
-        .map_err(|err| err.to_string())?;
     let to_mbox = to_address
         .parse::<Mailbox>()
         .map_err(|err| err.to_string())?;
 
-    let email_template = get_email_template(email_key, &state.db_pool)
-        .await
-        .map_err(|err| err.to_string())?;
     let email_body =
-        populate_email_template(&email_template, values).map_err(|err| err.to_string())?;
+        populate_email_template(&email_template.template, values).map_err(|err| err.to_string())?;
 
-    Message::builder()
-        .from(from_mbox)
-        .reply_to(replyto_mbox)
+    let builder = Message::builder()
+        .from(sender.from.clone())
+        .reply_to(sender.reply_to.clone())
         .to(to_mbox)
-        .subject(subject)
-        .singlepart(SinglePart::html(email_body))
-        .map_err(|err| err.to_string())
+        .subject(&email_template.subject);
+    match email_template.plaintext.as_deref() {
+        Some(plaintext) => {
+            let plaintext_body =
+                populate_plaintext_template(plaintext, values).map_err(|err| err.to_string())?;
+            builder.multipart(MultiPart::alternative_plain_html(
+                plaintext_body,
+                email_body,
+            ))
+        }
+        None => builder.singlepart(SinglePart::html(email_body)),
+    }
+    .map_err(|err| err.to_string())
+}
+
+pub async fn queue_email(
+    email_key: &str,
+    to_address: &str,
+    values: &EmailValues,
+    member_id: Option<i32>,
+    conn: &mut sqlx::PgConnection,
+) -> Result<i32, String> {
+    let email_template = get_email_template(email_key, &mut *conn)
+        .await
+        .map_err(|err| err.to_string())?;
+    let sender = get_email_sender(&mut *conn).await?;
+    let message = build_message(&email_template, &sender, to_address, values)?;
+    outbox::enqueue(
+        outbox::OutboxMessage {
+            email_key,
+            subject: &email_template.subject,
+            to_address,
+            member_id,
+            campaign_id: None,
+            message,
+        },
+        &mut *conn,
+    )
+    .await
+    .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_email_template;
+
+    #[test]
+    fn accepts_known_fields() {
+        assert_eq!(
+            validate_email_template(
+                "<p>Hi {first_name} {last_name | unescaped},</p>{{ if invite_url }}<a href=\"{invite_url}\">Join</a>{{ endif }}"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err = validate_email_template(
+            "Hi {first_name} {nickname}, {{ if not discord_id }}{{ endif }}",
+        )
+        .unwrap_err();
+        assert!(
+            err.starts_with("Unknown field(s) nickname, discord_id."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn accepts_names_bound_by_with_and_for() {
+        assert_eq!(
+            validate_email_template("{{ with first_name as name }}Hi {name}{{ endwith }}"),
+            Ok(())
+        );
+        let err = validate_email_template("{{ for code in verification_code }}{code}{{ endfor }}")
+            .unwrap_err();
+        assert!(!err.starts_with("Unknown field"), "{}", err);
+    }
+
+    #[test]
+    fn skips_escaped_braces() {
+        assert_eq!(
+            validate_email_template("<style>p \\{ color: red; }</style><p>{first_name}</p>"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_tags() {
+        assert!(validate_email_template("Hi {first_name").is_err());
+        assert!(validate_email_template("{{ if first_name }}Hi").is_err());
+    }
 }
@@ -0,0 +1,182 @@
+use lettre::{address::Envelope, Address, Message};
+
+use super::transport::build_mailer;
+
+const DELIVERY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+const DELIVERY_BATCH_SIZE: i64 = 50;
+const MAX_ATTEMPTS: i32 = 8;
+
+pub struct OutboxMessage<'a> {
+    pub email_key: &'a str,
+    pub subject: &'a str,
+    pub to_address: &'a str,
+    pub member_id: Option<i32>,
+    pub campaign_id: Option<i32>,
+    pub message: Message,
+}
+
+pub async fn enqueue(
+    outbox_message: OutboxMessage<'_>,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<i32, sqlx::Error> {
+    let envelope = outbox_message.message.envelope();
+    sqlx::query_scalar!(
+        r#"INSERT INTO email_outbox (member_id, campaign_id, email_key, to_address, subject, envelope_from, envelope_to, message)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id"#,
+        outbox_message.member_id,
+        outbox_message.campaign_id,
+        outbox_message.email_key,
+        outbox_message.to_address,
+        outbox_message.subject,
+        envelope.from().map(|address| address.to_string()),
+        &envelope
+            .to()
+            .iter()
+            .map(|address| address.to_string())
+            .collect::<Vec<_>>(),
+        outbox_message.message.formatted()
+    )
+    .fetch_one(db)
+    .await
+}
+
+struct PendingEmail {
+    id: i32,
+    envelope_from: Option<String>,
+    envelope_to: Vec<String>,
+    message: Vec<u8>,
+}
+
+impl PendingEmail {
+    fn envelope(&self) -> Result<Envelope, String> {
+        let from = self
+            .envelope_from
+            .as_deref()
+            .map(str::parse::<Address>)
+            .transpose()
+            .map_err(|err| err.to_string())?;
+        let to = self
+            .envelope_to
+            .iter()
+            .map(|address| address.parse::<Address>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.to_string())?;
+        Envelope::new(from, to).map_err(|err| err.to_string())
+    }
+}
+
+async fn record_failure(id: i32, err: &str, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    // Back off exponentially from a minute, capped at 12 hours between attempts
+    sqlx::query!(
+        r#"UPDATE email_outbox
+            SET
+                attempts = attempts + 1,
+                last_error = $2,
+                status = CASE WHEN attempts + 1 >= $3 THEN 'failed' ELSE 'queued' END,
+                next_attempt_at = NOW() + LEAST(INTERVAL '1 minute' * POWER(2, attempts), INTERVAL '12 hours')
+            WHERE id = $1"#,
+        id,
+        err,
+        MAX_ATTEMPTS
+    )
+    .execute(db_pool)
+    .await
+    .map(|_| ())
+}
+
+pub async fn deliver_pending(state: &crate::AppState) -> Result<usize, String> {
+    let pending = sqlx::query_as!(
+        PendingEmail,
+        r#"SELECT id, envelope_from, envelope_to, message
+            FROM email_outbox
+            WHERE status = 'queued' AND next_attempt_at <= NOW()
+            ORDER BY id
+            LIMIT $1"#,
+        DELIVERY_BATCH_SIZE
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|err| err.to_string())?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let mailer = match build_mailer(state).await {
+        Ok(mailer) => mailer,
+        Err(err) => {
+            for email in &pending {
+                record_failure(email.id, &err, &state.db_pool)
+                    .await
+                    .map_err(|err| err.to_string())?;
+            }
+            return Err(err);
+        }
+    };
+
+    let mut delivered = 0;
+    for email in pending {
+        let sent = match email.envelope() {
+            Ok(envelope) => mailer.send_raw(&envelope, &email.message).await,
+            Err(err) => Err(err),
+        };
+        match sent {
+            Ok(_) => {
+                sqlx::query!(
+                    r#"UPDATE email_outbox
+                        SET status = 'sent', attempts = attempts + 1, last_error = NULL, sent_at = NOW()
+                        WHERE id = $1"#,
+                    email.id
+                )
+                .execute(&state.db_pool)
+                .await
+                .map_err(|err| err.to_string())?;
+                delivered += 1;
+            }
+            Err(err) => {
+                tracing::warn!("Sending email {} failed: {}", email.id, err);
+                record_failure(email.id, &err, &state.db_pool)
+                    .await
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+pub async fn resend(id: i32, db: impl sqlx::PgExecutor<'_>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE email_outbox
+            SET status = 'queued', attempts = 0, last_error = NULL, next_attempt_at = NOW()
+            WHERE id = $1"#,
+        id
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn resend_failed_campaign(
+    campaign_id: i32,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE email_outbox
+            SET status = 'queued', attempts = 0, last_error = NULL, next_attempt_at = NOW()
+            WHERE campaign_id = $1 AND status = 'failed'"#,
+        campaign_id
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+pub async fn deliver_periodically(state: crate::AppState) {
+    let mut interval = tokio::time::interval(DELIVERY_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = deliver_pending(&state).await {
+            tracing::warn!("Delivering queued email failed: {}", err);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use lettre::{
+    address::Envelope,
+    transport::{
+        smtp::{
+            authentication::{Credentials, Mechanism},
+            client::Tls,
+        },
+        stub::AsyncStubTransport,
+    },
+    AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+use oauth2::{AccessToken, RefreshToken, TokenResponse};
+use shuttle_runtime::SecretStore;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SmtpSecurity {
+    Tls,
+    StartTls,
+    None,
+}
+
+// MAIL_TRANSPORT: gmail (default; GMAIL_USERNAME, GMAIL_OAUTH_REFRESH_TOKEN), smtp (SMTP_HOST,
+// SMTP_PORT, SMTP_SECURITY = starttls/tls/none, SMTP_USERNAME, SMTP_PASSWORD), file (MAIL_FILE_DIR)
+// or memory
+#[derive(Clone)]
+pub enum MailTransport {
+    GmailOAuth {
+        username: String,
+    },
+    Smtp {
+        host: String,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+    },
+    File {
+        dir: PathBuf,
+    },
+    Memory(AsyncStubTransport),
+}
+
+impl MailTransport {
+    pub fn from_secrets(secret_store: &SecretStore) -> Result<Self, String> {
+        let required = |key: &str| secret_store.get(key).ok_or(format!("{} is not set", key));
+        match secret_store.get("MAIL_TRANSPORT").as_deref() {
+            None | Some("gmail") => Ok(Self::GmailOAuth {
+                username: required("GMAIL_USERNAME")?,
+            }),
+            Some("smtp") => Ok(Self::Smtp {
+                host: required("SMTP_HOST")?,
+                port: secret_store
+                    .get("SMTP_PORT")
+                    .map(|port| port.parse::<u16>())
+                    .transpose()
+                    .map_err(|err| format!("SMTP_PORT: {}", err))?,
+                security: match secret_store.get("SMTP_SECURITY").as_deref() {
+                    None | Some("starttls") => SmtpSecurity::StartTls,
+                    Some("tls") => SmtpSecurity::Tls,
+                    Some("none") => SmtpSecurity::None,
+                    Some(other) => return Err(format!("Unknown SMTP_SECURITY {}", other)),
+                },
+                credentials: match secret_store.get("SMTP_USERNAME") {
+                    Some(username) => Some((username, required("SMTP_PASSWORD")?)),
+                    None => None,
+                },
+            }),
+            Some("file") => Ok(Self::File {
+                dir: secret_store
+                    .get("MAIL_FILE_DIR")
+                    .unwrap_or_else(|| String::from("emails"))
+                    .into(),
+            }),
+            Some("memory") => Ok(Self::Memory(AsyncStubTransport::new_ok())),
+            Some(other) => Err(format!("Unknown MAIL_TRANSPORT {}", other)),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Self::GmailOAuth { username } => format!("Gmail as {}", username),
+            Self::Smtp { host, port, .. } => match port {
+                Some(port) => format!("SMTP via {}:{}", host, port),
+                None => format!("SMTP via {}", host),
+            },
+            Self::File { dir } => format!(".eml files in {}", dir.display()),
+            Self::Memory(_) => String::from("memory (not delivered)"),
+        }
+    }
+}
+
+pub enum Mailer {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    File(AsyncFileTransport<Tokio1Executor>),
+    Memory(AsyncStubTransport),
+}
+
+impl Mailer {
+    pub async fn send_raw(&self, envelope: &Envelope, email: &[u8]) -> Result<(), String> {
+        match self {
+            Self::Smtp(transport) => transport
+                .send_raw(envelope, email)
+                .await
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Self::File(transport) => transport
+                .send_raw(envelope, email)
+                .await
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Self::Memory(transport) => transport
+                .send_raw(envelope, email)
+                .await
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+async fn get_access_token(state: &crate::AppState) -> Result<AccessToken, String> {
+    state
+        .google_oauth
+        .exchange_refresh_token(&RefreshToken::new(
+            state
+                .secret_store
+                .get("GMAIL_OAUTH_REFRESH_TOKEN")
+                .ok_or("GMAIL_OAUTH_REFRESH_TOKEN is not set")?,
+        ))
+        .request_async(oauth2::reqwest::async_http_client)
+        .await
+        .map(|resp| resp.access_token().to_owned())
+        .map_err(|err| err.to_string())
+}
+
+pub async fn build_mailer(state: &crate::AppState) -> Result<Mailer, String> {
+    match &state.mail_transport {
+        MailTransport::GmailOAuth { username } => Ok(Mailer::Smtp(
+            AsyncSmtpTransport::<Tokio1Executor>::relay("smtp.gmail.com")
+                .map_err(|err| err.to_string())?
+                .authentication(vec![Mechanism::Xoauth2])
+                .credentials(Credentials::new(
+                    username.clone(),
+                    get_access_token(state).await?.secret().clone(),
+                ))
+                .build(),
+        )),
+        MailTransport::Smtp {
+            host,
+            port,
+            security,
+            credentials,
+        } => {
+            let mut builder = match security {
+                SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+                SmtpSecurity::StartTls => {
+                    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                }
+                SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                    host,
+                )),
+            }
+            .map_err(|err| err.to_string())?;
+            if *security == SmtpSecurity::None {
+                builder = builder.tls(Tls::None);
+            }
+            if let Some(port) = port {
+                builder = builder.port(*port);
+            }
+            if let Some((username, password)) = credentials {
+                builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+            }
+            Ok(Mailer::Smtp(builder.build()))
+        }
+        MailTransport::File { dir } => {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(|err| err.to_string())?;
+            Ok(Mailer::File(AsyncFileTransport::new(dir)))
+        }
+        MailTransport::Memory(transport) => Ok(Mailer::Memory(transport.clone())),
+    }
+}
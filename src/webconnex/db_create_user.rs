@@ -11,18 +11,20 @@ pub struct SqlCreateResponse {
 
 pub async fn create_user(
     event: &request_payload::EventDetails,
-    state: &crate::AppState,
+    db: impl sqlx::PgExecutor<'_>,
 ) -> Result<SqlCreateResponse, Response> {
     sqlx::query_as!(
         SqlCreateResponse,
         r#"INSERT INTO members (email, first_name, last_name)
-        VALUES ($1, $2, $3)
+        SELECT $1, $2, $3
+        WHERE member_id_by_email($1) IS NULL
+        ON CONFLICT DO NOTHING
         RETURNING id"#,
         event.billing.email.to_lowercase(),
         event.billing.name.first,
         event.billing.name.last
     )
-    .fetch_one(&state.db_pool)
+    .fetch_one(db)
     .await
     .map_err_response(ErrorResponse::InternalServerError)
 }
@@ -12,20 +12,20 @@ pub struct InsertTransactionResponse {
 
 pub async fn insert_transaction(
     body: &request_payload::EventDetails,
-    state: &crate::AppState,
+    db: impl sqlx::PgExecutor<'_>,
 ) -> Result<InsertTransactionResponse, Response> {
     sqlx::query_as!(
         InsertTransactionResponse,
-        r#"INSERT INTO payments (member_id, amount_paid, payment_method, transaction_id)
-            SELECT               id,        $2,          'webconnex',    $3
+        r#"INSERT INTO payments (member_id, amount_paid, duration_months,         plan_id,             payment_method, transaction_id)
+            SELECT               id,        $2,          duration_for_amount($2), plan_for_amount($2), 'webconnex',    $3
             FROM members
-            WHERE email = $1
+            WHERE id = member_id_by_email($1)
         RETURNING id, member_id"#,
         body.billing.email.to_lowercase(),
         body.total,
         body.transaction_id
     )
-    .fetch_one(&state.db_pool)
+    .fetch_one(db)
     .await
     .map_err_response(ErrorResponse::InternalServerError)
 }
@@ -1,57 +1,18 @@
 use axum::{extract::State, response::Response, Json};
-use lettre::AsyncTransport;
 
 use crate::{
-    discord::create_invite,
+    db::audit_events::AuditEvent,
+    discord::invites::send_welcome_email,
     err_responses::{ErrorResponse, MapErrorResponse},
-    send_email::{build_mailer, build_message, EmailValues},
+    send_email::EmailValues,
 };
 
 use super::{
     db_create_user::{create_user, SqlCreateResponse},
     db_insert_transaction::{insert_transaction, InsertTransactionResponse},
-    request_payload::{EventDetails, RequestPayload},
+    request_payload::RequestPayload,
 };
 
-async fn send_emails(state: &crate::AppState, event: &EventDetails) -> Result<(), Response> {
-    let invite_url = create_invite(
-        Some(&format!(
-            "New member automated invite (GivingFuel order #{}, Email {})",
-            event.transaction_id, event.billing.email
-        )),
-        state,
-    )
-    .await
-    .map_err_response(ErrorResponse::InternalServerError)?;
-
-    let mailer = build_mailer(state)
-        .await
-        .map_err_response(ErrorResponse::InternalServerError)?;
-    let values = EmailValues {
-        first_name: event.billing.name.first.clone(),
-        last_name: event.billing.name.last.clone(),
-        invite_url,
-        email: event.billing.email.clone(),
-        ..Default::default()
-    };
-
-    mailer
-        .send(
-            build_message(
-                "discord",
-                "Psychedelic Club Discord",
-                &event.billing.email,
-                &values,
-                state,
-            )
-            .await
-            .map_err_response(ErrorResponse::InternalServerError)?,
-        )
-        .await
-        .map_err_response(ErrorResponse::InternalServerError)?;
-    Ok(())
-}
-
 #[derive(serde::Serialize)]
 pub struct ResponseBody {
     create_user: Option<SqlCreateResponse>,
@@ -62,13 +23,100 @@ pub async fn webhook_handler(
     State(state): State<crate::AppState>,
     Json(RequestPayload { data: event }): Json<RequestPayload>,
 ) -> Result<axum::Json<ResponseBody>, Response> {
-    let create_response = create_user(&event, &state).await;
-    let insert_response = insert_transaction(&event, &state).await?;
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
+    let create_response = create_user(&event, &mut *transaction).await;
+    let insert_response = insert_transaction(&event, &mut *transaction).await?;
 
-    send_emails(&state, &event).await?;
+    AuditEvent {
+        member_id: Some(insert_response.member_id),
+        action: "webconnex.new_member",
+        reason: Some(&format!("GivingFuel transaction #{}", event.transaction_id)),
+        after: serde_json::to_value(&insert_response).ok(),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
+
+    send_welcome_email(
+        insert_response.member_id,
+        &event.billing.email,
+        EmailValues {
+            first_name: event.billing.name.first.clone(),
+            last_name: event.billing.name.last.clone(),
+            email: event.billing.email.clone(),
+            ..Default::default()
+        },
+        &format!(
+            "New member automated invite (GivingFuel order #{}, Email {})",
+            event.transaction_id, event.billing.email
+        ),
+        &state,
+    )
+    .await;
 
     Ok(Json(ResponseBody {
         create_user: create_response.ok(),
         insert_transaction: insert_response,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::discord::client::DisabledDiscord;
+
+    #[sqlx::test]
+    async fn skips_the_welcome_email_without_an_invite(db_pool: sqlx::PgPool) {
+        let state = crate::AppState::for_tests(db_pool, Arc::new(DisabledDiscord));
+        sqlx::query!(
+            r#"INSERT INTO email_addresses (id, value)
+                VALUES ('from', 'MDMA <mdma@example.com>'), ('replyto', 'board@example.com')"#
+        )
+        .execute(&state.db_pool)
+        .await
+        .unwrap();
+        let payload = serde_json::from_value(serde_json::json!({
+            "data": {
+                "total": 25,
+                "billing": {
+                    "email": "new@example.com",
+                    "name": { "first": "New", "last": "Member" },
+                },
+                "transactionId": 42,
+            }
+        }))
+        .unwrap();
+
+        let Json(response) = webhook_handler(State(state.clone()), Json(payload))
+            .await
+            .unwrap();
+
+        let queued = sqlx::query_scalar!("SELECT email_key FROM email_outbox")
+            .fetch_all(&state.db_pool)
+            .await
+            .unwrap();
+        assert!(queued.is_empty());
+        let actions = sqlx::query_scalar!(
+            "SELECT action FROM audit_events WHERE member_id = $1 ORDER BY id",
+            response.insert_transaction.member_id
+        )
+        .fetch_all(&state.db_pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            actions,
+            vec!["webconnex.new_member", "member.discord_invite_failed"]
+        );
+    }
+}
@@ -1,6 +1,11 @@
 use axum::{extract::State, response::Response, Json};
 use serde::Serialize;
 
+use crate::{
+    db::audit_events::AuditEvent,
+    err_responses::{ErrorResponse, MapErrorResponse},
+};
+
 use super::{db_insert_transaction::insert_transaction, request_payload::RequestPayload};
 
 #[derive(Serialize)]
@@ -13,7 +18,27 @@ pub async fn webhook_handler(
     State(state): State<crate::AppState>,
     Json(RequestPayload { data: event }): Json<RequestPayload>,
 ) -> Result<axum::Json<ResponseBody>, Response> {
-    let insert_response = insert_transaction(&event, &state).await?;
+    let mut transaction = state
+        .db_pool
+        .begin()
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
+    let insert_response = insert_transaction(&event, &mut *transaction).await?;
+
+    AuditEvent {
+        member_id: Some(insert_response.member_id),
+        action: "webconnex.payment",
+        reason: Some(&format!("GivingFuel transaction #{}", event.transaction_id)),
+        after: serde_json::to_value(&insert_response).ok(),
+        ..Default::default()
+    }
+    .record(&mut *transaction)
+    .await
+    .map_err_response(ErrorResponse::InternalServerError)?;
+    transaction
+        .commit()
+        .await
+        .map_err_response(ErrorResponse::InternalServerError)?;
 
     Ok(Json(ResponseBody {
         member_id: insert_response.member_id,